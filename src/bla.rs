@@ -0,0 +1,422 @@
+//! Bivariate linear approximation (BLA), for skipping iterations anywhere in a pixel's orbit.
+//!
+//! Whenever δz is small enough next to Z that δz² is negligible, a perturbation step is linear in δz and δc:
+//!
+//! ```text
+//! δz' ≈ 2Zδz + δc
+//! ```
+//!
+//! Following one linear step with another gives a third, `δz ↦ Aδz + Bδc`, so runs of iterations can be merged together,
+//! along with the largest |δz| they're accurate for:
+//!
+//! ```text
+//! A = A₂A₁
+//! B = A₂B₁ + B₂
+//! R = min(R₁, (R₂ - |B₁||δc|) / |A₁|)
+//! ```
+//!
+//! The table has a level for every power-of-two length of step, each of which has a step starting at every iteration that's a multiple of its length (counting from 1),
+//! and pixels take the longest step they're within the radius of.
+//! Unlike series approximation, this can skip iterations anywhere in the orbit rather than only at the start,
+//! which helps a lot in locations where the series approximation breaks down early.
+//!
+//! Like the series approximation, A and B can easily be too large or small for an `f64`, so they're stored as `FloatExp`s.
+
+use bytemuck::Pod;
+use bytemuck::Zeroable;
+
+use crate::coloring;
+use crate::formula::Formula;
+use crate::num::FloatExp;
+use crate::perturbation::ReferenceOrbit;
+use crate::series;
+use crate::series::ComplexExp;
+use crate::Pixel;
+
+/// How small δz² has to be relative to the linear terms for a step to be trusted.
+///
+/// This is around the precision of an `f32`, so that the terms being left out are smaller than the rounding error the GPU will introduce anyway.
+const TOLERANCE: f64 = 1.0 / (1 << f32::MANTISSA_DIGITS) as f64;
+
+/// A linear approximation of some number of iterations: `δz ↦ Aδz + Bδc`.
+#[derive(Debug, Clone, Copy)]
+pub struct Bla {
+    pub a: ComplexExp,
+    pub b: ComplexExp,
+    /// The largest |δz| this is accurate for.
+    pub radius: FloatExp,
+}
+
+impl Bla {
+    /// The approximation of the single iteration from `z`, where B is 0 for a Julia set since δc is.
+    fn step(z: ComplexExp, julia: bool) -> Self {
+        let b = if julia { 0.0 } else { 1.0 };
+        Self {
+            a: series::add(z, z),
+            b: [FloatExp::from(b), FloatExp::default()],
+            // δz² is negligible next to 2Zδz while |δz| is below ε|2Z|, so use half that, ε|Z|, to leave some room for δc.
+            radius: FloatExp::from(TOLERANCE) * series::norm_sqr(z).sqrt(),
+        }
+    }
+
+    /// Merges this approximation with `next`, which is taken straight afterwards, for pixels up to `dc_max` from the reference.
+    fn then(&self, next: &Self, dc_max: FloatExp) -> Self {
+        let zero = FloatExp::default();
+        let a_len = series::norm_sqr(self.a).sqrt();
+        let b_len = series::norm_sqr(self.b).sqrt();
+
+        // `next` is accurate once δz after this step is within its radius, which is at most |A||δz| + |B||δc|.
+        let next_radius = if a_len == zero {
+            zero
+        } else {
+            max(next.radius - b_len * dc_max, zero) / a_len
+        };
+
+        Self {
+            a: series::mul(next.a, self.a),
+            b: series::add(series::mul(next.a, self.b), next.b),
+            radius: min(self.radius, next_radius),
+        }
+    }
+}
+
+/// A `Bla` in the form the GPU expects (`Bla` in `perturbation.wgsl`).
+#[derive(Clone, Copy, Zeroable, Pod, Debug)]
+#[repr(C)]
+pub struct GpuBla {
+    a: [f32; 2],
+    b: [f32; 2],
+    a_exponent: i32,
+    b_exponent: i32,
+    radius: f32,
+    radius_exponent: i32,
+}
+
+impl From<&Bla> for GpuBla {
+    fn from(bla: &Bla) -> Self {
+        let (a, a_exponent) = series::to_gpu(bla.a);
+        let (b, b_exponent) = series::to_gpu(bla.b);
+        let (radius, radius_exponent) = bla.radius.to_f32_parts();
+        Self {
+            a,
+            b,
+            a_exponent,
+            b_exponent,
+            radius,
+            radius_exponent,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlaTable {
+    /// The steps of each length, starting from length 1 and doubling each level.
+    ///
+    /// Step `j` of level `k` starts at iteration `1 + j * 2^k`; the first level has a step for every iteration from 1 until the second last value in the orbit,
+    /// and each level after that has half as many (rounded down).
+    pub levels: Vec<Vec<Bla>>,
+}
+
+impl BlaTable {
+    /// Builds the table for `reference`, for pixels up to `dc_max` away from it on the complex plane.
+    pub fn new(reference: &ReferenceOrbit, dc_max: FloatExp) -> Self {
+        // There's no step from Z₀, since every pixel's δz₁ is just δc anyway (or comes straight from the series approximation for a Julia set).
+        // A and B are built from the full-precision orbit, since they grow by a factor of |2Z| every iteration.
+        let steps = reference
+            .points
+            .get(1..reference.points.len() - 1)
+            .unwrap_or_default();
+
+        let mut levels = vec![steps
+            .iter()
+            .map(|&z| Bla::step(z, reference.julia))
+            .collect::<Vec<_>>()];
+        while levels.last().unwrap().len() >= 2 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks_exact(2)
+                .map(|pair| pair[0].then(&pair[1], dc_max))
+                .collect();
+            levels.push(level);
+        }
+
+        Self { levels }
+    }
+
+    /// Finds the longest step which can be taken from iteration `n` by a pixel whose |δz| is `dz`, without going past `iterations`,
+    /// returning it along with the number of iterations it skips.
+    ///
+    /// This has to match `bla_lookup` in `perturbation.wgsl`.
+    pub fn lookup(&self, n: u32, dz: FloatExp, iterations: u32) -> Option<(&Bla, u32)> {
+        if n == 0 {
+            return None;
+        }
+
+        let mut found = None;
+        for (level, steps) in self.levels.iter().enumerate() {
+            let len = 1 << level;
+            let start = n - 1;
+            if !start.is_multiple_of(len) || n + len > iterations {
+                break;
+            }
+
+            // Longer steps never have larger radii, so there's no point looking any further once one's out of range.
+            match steps.get((start / len) as usize) {
+                Some(bla) if dz < bla.radius => found = Some((bla, len)),
+                _ => break,
+            }
+        }
+
+        found
+    }
+
+    /// The number of levels the GPU should look through (there's no point in it looking at empty ones).
+    pub fn gpu_levels(&self) -> u32 {
+        self.levels.iter().filter(|level| !level.is_empty()).count() as u32
+    }
+
+    /// Flattens the table into what the GPU expects: every level one after the other.
+    pub fn gpu_entries(&self) -> Vec<GpuBla> {
+        self.levels.iter().flatten().map(GpuBla::from).collect()
+    }
+}
+
+/// Iterates a pixel `dc` away from `reference` on the complex plane, skipping iterations using `table` wherever possible,
+/// until it gets further than `bailout` from the origin. For a Julia set, `dc` is the pixel's δz₀ instead.
+/// The distance estimate comes out in units of `pixel_size`, like the GPU's.
+///
+/// This is a CPU version of what `perturbation.wgsl` does (without series approximation or glitch detection), for checking the GPU's results against.
+pub fn iterate(
+    reference: &ReferenceOrbit,
+    table: &BlaTable,
+    dc: ComplexExp,
+    pixel_size: FloatExp,
+    iterations: u32,
+    bailout: f32,
+) -> Pixel {
+    let zero = [FloatExp::default(); 2];
+    let one = [FloatExp::from(1.0), FloatExp::default()];
+    // dz/dc, for estimating the distance to the set, or dz/dz₀ for a Julia set, which starts at 1 and has nothing added.
+    let (mut dz, dc, mut derivative, derivative_offset) = if reference.julia {
+        (dc, zero, one, zero)
+    } else {
+        (zero, dc, zero, one)
+    };
+    let mut n = 0;
+    let mut z_full = [0.0; 2];
+    let mut norm = 0.0;
+    while n < iterations && n as usize + 1 < reference.orbit.len() {
+        let [real, imag] = reference.orbit[n as usize];
+        let z = [FloatExp::from(real as f64), FloatExp::from(imag as f64)];
+
+        match table.lookup(n, series::norm_sqr(dz).sqrt(), iterations) {
+            Some((bla, len)) => {
+                // (dz/dc)' = A dz/dc + B
+                derivative = series::add(series::mul(bla.a, derivative), bla.b);
+                dz = series::add(series::mul(bla.a, dz), series::mul(bla.b, dc));
+                n += len;
+            }
+            None => {
+                // (dz/dc)' = 2(Z + δz) dz/dc + 1
+                let full = series::add(z, dz);
+                derivative = series::add(
+                    series::mul(series::add(full, full), derivative),
+                    derivative_offset,
+                );
+
+                // δz' = (2Z + δz)δz + δc
+                dz = series::add(series::mul(series::add(series::add(z, z), dz), dz), dc);
+                n += 1;
+            }
+        }
+
+        let [real, imag] = reference.orbit[n as usize];
+        z_full = [real as f64 + dz[0].to_f64(), imag as f64 + dz[1].to_f64()];
+        norm = z_full[0] * z_full[0] + z_full[1] * z_full[1];
+        if norm >= bailout as f64 * bailout as f64 {
+            break;
+        }
+    }
+
+    // The GPU measures the derivative in pixels, so multiply it by the pixel size to match.
+    let (slope, distance, angle) = if n < iterations {
+        let derivative = [derivative[0] * pixel_size, derivative[1] * pixel_size];
+        let slope = coloring::smooth_slope(
+            [z_full[0] as f32, z_full[1] as f32],
+            [derivative[0].to_f64() as f32, derivative[1].to_f64() as f32],
+            Formula::MANDELBROT.log_degree(),
+        );
+        let distance = FloatExp::from(coloring::distance_estimate(norm as f32, 1.0) as f64)
+            / series::norm_sqr(derivative).sqrt();
+        let angle = z_full[1].atan2(z_full[0]) as f32;
+        (slope, distance.to_f64() as f32, angle)
+    } else {
+        ([0.0; 2], 0.0, 0.0)
+    };
+
+    Pixel {
+        iters: n,
+        flags: 0,
+        period: 0,
+        norm: norm as f32,
+        slope,
+        distance,
+        trap: 0.0,
+        stripe: 0.0,
+        angle,
+        multiplier: [0.0; 2],
+        atom: 0,
+        misiurewicz: 0,
+    }
+}
+
+fn max(a: FloatExp, b: FloatExp) -> FloatExp {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+fn min(a: FloatExp, b: FloatExp) -> FloatExp {
+    if a < b {
+        a
+    } else {
+        b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::num::Complex;
+
+    /// A reference just outside the neck between the main cardioid and the period-2 bulb, which takes hundreds of iterations to escape.
+    fn reference(iterations: u32) -> ReferenceOrbit {
+        let mut c = Complex::from([-0.75, 0.005]);
+        c.set_precision(4);
+        ReferenceOrbit::new(c, iterations, 2.0)
+    }
+
+    #[test]
+    fn matches_plain_perturbation() {
+        let iterations = 2000;
+        let reference = reference(iterations);
+        assert!(reference.escaped(iterations));
+
+        let pixel_size = FloatExp::from(1e-20);
+        let radius = 600.0;
+        let table = BlaTable::new(&reference, FloatExp::from(radius) * pixel_size);
+        let empty = BlaTable { levels: Vec::new() };
+
+        for &[x, y] in &[[0.0, 0.0], [1.0, 0.0], [-300.0, 200.0], [424.0, -424.0]] {
+            let dc = [
+                FloatExp::from(x) * pixel_size,
+                FloatExp::from(y) * pixel_size,
+            ];
+            let fast = iterate(&reference, &table, dc, pixel_size, iterations, 2.0);
+            let slow = iterate(&reference, &empty, dc, pixel_size, iterations, 2.0);
+
+            assert_eq!(fast.iters, slow.iters, "({}, {})", x, y);
+            assert!(
+                (fast.norm - slow.norm).abs() <= 1e-3 * slow.norm,
+                "({}, {}): |z|² was {} rather than {}",
+                x,
+                y,
+                fast.norm,
+                slow.norm
+            );
+            assert!(
+                (fast.distance - slow.distance).abs() <= 1e-2 * slow.distance,
+                "({}, {}): distance was {} rather than {}",
+                x,
+                y,
+                fast.distance,
+                slow.distance
+            );
+        }
+    }
+
+    #[test]
+    fn skips_iterations() {
+        let iterations = 2000;
+        let reference = reference(iterations);
+        let pixel_size = FloatExp::from(1e-20);
+        let table = BlaTable::new(&reference, FloatExp::from(600.0) * pixel_size);
+
+        // A pixel right next to the reference has a tiny δz for most of its orbit, so it should be able to take long steps.
+        let longest = (1..iterations)
+            .filter_map(|n| table.lookup(n, pixel_size, iterations))
+            .map(|(_, len)| len)
+            .max();
+        assert!(longest.unwrap_or(0) >= 64, "longest step was {:?}", longest);
+    }
+
+    #[test]
+    fn lookup_stays_in_bounds() {
+        let reference = reference(2000);
+        let table = BlaTable::new(&reference, FloatExp::from(1e-18));
+        // Anything smaller than every radius, so that the longest steps get taken.
+        let dz = FloatExp::new(1.0, -1000);
+        for &iterations in &[1, 2, 7, 64, 100, 2000] {
+            for n in 0..iterations {
+                if let Some((_, len)) = table.lookup(n, dz, iterations) {
+                    assert!(
+                        n + len <= iterations,
+                        "step of {} from {} went past {}",
+                        len,
+                        n,
+                        iterations
+                    );
+                    assert!(
+                        ((n + len) as usize) < reference.orbit.len(),
+                        "step of {} from {} went past the end of the orbit",
+                        len,
+                        n
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn distance_matches_direct_iteration() {
+        let c = [-0.75, 0.1];
+        let reference = ReferenceOrbit::new(Complex::from(c), 1000, 256.0);
+        let table = BlaTable::new(&reference, FloatExp::from(1e-2));
+        let pixel_size = 1e-7;
+
+        for &[x, y] in &[[0.0, 0.0], [30.0, -20.0]] {
+            let dc = [
+                FloatExp::from(x * pixel_size),
+                FloatExp::from(y * pixel_size),
+            ];
+            let perturbed = iterate(
+                &reference,
+                &table,
+                dc,
+                FloatExp::from(pixel_size),
+                1000,
+                256.0,
+            );
+            let direct = Formula::MANDELBROT.iterate(
+                [c[0] + x * pixel_size, c[1] + y * pixel_size],
+                None,
+                1000,
+                256.0,
+                pixel_size,
+            );
+
+            assert_eq!(perturbed.iters, direct.iters);
+            assert!(
+                (perturbed.distance - direct.distance).abs() <= 1e-3 * direct.distance,
+                "({}, {}): distance was {} rather than {}",
+                x,
+                y,
+                perturbed.distance,
+                direct.distance
+            );
+        }
+    }
+}
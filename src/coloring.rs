@@ -0,0 +1,493 @@
+//! Turning the results of iterating pixels into colours.
+//!
+//! This mirrors what `colorize.wgsl` does, so that images rendered on the CPU come out the same.
+
+use std::f32::consts::FRAC_PI_4;
+use std::f32::consts::TAU;
+
+use crate::orbit;
+use crate::palette::Palette;
+use crate::Pixel;
+
+/// The default bailout radius.
+///
+/// Anything above 2 works for telling whether pixels escape, but smooth colouring needs a much larger one for the
+/// fractional part of the iteration count to come out right.
+pub const DEFAULT_BAILOUT: f32 = 256.0;
+/// The largest bailout radius that can be used, since fixed point can't square anything much larger without overflowing.
+pub const MAX_BAILOUT: f32 = 32768.0;
+
+/// The number of field lines drawn per turn around the set with `Coloring::FieldLines` (`FIELD_LINES` in `colorize.wgsl`).
+pub const FIELD_LINES: f32 = 16.0;
+/// How wide field lines are, as a fraction of the gap between them.
+pub const FIELD_LINE_WIDTH: f32 = 0.2;
+
+/// How many domains `Coloring::Atom` and `Coloring::Misiurewicz` fit into the palette before it starts repeating
+/// (`DOMAIN_PALETTE_PERIODS` in `colorize.wgsl`).
+pub const DOMAIN_PALETTE_PERIODS: f32 = 12.0;
+
+/// How tightly focused the highlights from `Lighting` are.
+const SHININESS: f32 = 32.0;
+
+/// The number of bins in the histogram used for `Coloring::Histogram` (`HISTOGRAM_BINS` in `common.wgsl`).
+pub const HISTOGRAM_BINS: u32 = 4096;
+
+/// How pixels are coloured (the `COLORING_*` constants in `common.wgsl`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coloring {
+    /// By how many iterations they took to escape, which has visible bands between each iteration count.
+    Discrete = 0,
+    /// By a continuous version of the iteration count, which takes into account how far past the bailout they ended up.
+    Smooth = 1,
+    /// Like `Smooth`, but mapped through the cumulative histogram of the whole image,
+    /// so that the colours get spread evenly across the palette no matter how deep the zoom is.
+    Histogram = 2,
+    /// Like `Smooth`, but shaded darker the closer pixels are to the set,
+    /// so that filaments thinner than a pixel still show up instead of disappearing between pixels.
+    Distance = 3,
+    /// By how close their orbits got to an orbit trap (see `orbit.rs`).
+    Trap = 4,
+    /// By the average of sin(frequency × arg z) over their orbits, which draws stripes that follow the set's outline.
+    Stripes = 5,
+    /// Like `Smooth`, but with pixels whose final z has a negative imaginary part blacked out,
+    /// which splits each band between iteration counts into cells along the external rays.
+    Binary = 6,
+    /// Like `Smooth`, but with dark lines along the external rays at evenly spaced angles of the final z.
+    FieldLines = 7,
+    /// By atom domain: the iteration where |z| was smallest.
+    /// Each minibrot of period p sits in a domain of p, so these show where the minibrots are.
+    ///
+    /// Unlike the others, this colours the inside of the set too.
+    Atom = 8,
+    /// By Misiurewicz domain: the iteration q where |z_{q+1} - z_q| was smallest, which is the preperiod of the nearest
+    /// Misiurewicz point of period 1.
+    ///
+    /// Unlike the others, this colours the inside of the set too.
+    Misiurewicz = 9,
+}
+
+impl Coloring {
+    /// Where `pixel`, which was iterated with a limit of `iterations` and a bailout radius of `bailout`, lands in the palette
+    /// before its offset and scale are applied, or `None` if it's in the set (and this isn't a domain colouring).
+    /// `log_degree` is the formula's `Formula::log_degree`.
+    ///
+    /// `histogram` is only used for `Histogram` colouring, which comes out the same as `Smooth` without it.
+    pub fn value(
+        self,
+        pixel: &Pixel,
+        iterations: u32,
+        bailout: f32,
+        log_degree: f32,
+        histogram: Option<&Histogram>,
+    ) -> Option<f32> {
+        match self {
+            Self::Atom => return Some((pixel.atom.max(1) - 1) as f32 / DOMAIN_PALETTE_PERIODS),
+            Self::Misiurewicz => {
+                return Some((pixel.misiurewicz.max(1) - 1) as f32 / DOMAIN_PALETTE_PERIODS)
+            }
+            _ => {}
+        }
+
+        if pixel.iters >= iterations {
+            return None;
+        }
+
+        let iters = match self {
+            Self::Discrete => pixel.iters as f32,
+            Self::Smooth | Self::Histogram | Self::Distance | Self::Binary | Self::FieldLines => {
+                smooth_iterations(pixel, bailout, log_degree)
+            }
+            // These aren't iteration counts, so they don't get divided by the limit.
+            Self::Trap => return Some(pixel.trap),
+            Self::Stripes => return Some(pixel.stripe),
+            Self::Atom | Self::Misiurewicz => unreachable!(),
+        };
+
+        let value = iters / iterations as f32;
+        match (self, histogram) {
+            (Self::Histogram, Some(histogram)) => Some(histogram.equalize(value)),
+            _ => Some(value),
+        }
+    }
+
+    /// The `TRACK_*` flags from `orbit.rs` for the values this colouring builds up along the whole orbit,
+    /// which means series approximation and BLA can't be used to skip iterations if there are any.
+    pub fn orbit_tracking(self) -> u32 {
+        match self {
+            Self::Trap => orbit::TRACK_TRAP,
+            Self::Stripes => orbit::TRACK_STRIPES,
+            Self::Atom => orbit::TRACK_ATOM,
+            Self::Misiurewicz => orbit::TRACK_MISIUREWICZ,
+            _ => 0,
+        }
+    }
+
+    /// The linear RGB colour of `pixel` with `palette`, the same as `colorize.wgsl` gives it.
+    ///
+    /// Pixels in the set come out black unless this is a domain colouring; `InteriorColoring::color` gives them their colour otherwise.
+    #[allow(clippy::too_many_arguments)]
+    pub fn color(
+        self,
+        pixel: &Pixel,
+        iterations: u32,
+        bailout: f32,
+        log_degree: f32,
+        palette: &Palette,
+        histogram: Option<&Histogram>,
+        lighting: Option<&Lighting>,
+    ) -> [f32; 3] {
+        let color = match self.value(pixel, iterations, bailout, log_degree, histogram) {
+            Some(value) => palette.color(value),
+            None => return [0.0; 3],
+        };
+
+        let color = match self {
+            // Fade to black over the last pixel before the set.
+            Self::Distance => color.map(|component| component * pixel.distance.clamp(0.0, 1.0)),
+            Self::Binary if pixel.angle < 0.0 => [0.0; 3],
+            Self::FieldLines => {
+                let shade = field_line_shade(pixel.angle);
+                color.map(|component| component * shade)
+            }
+            // Domains aren't lit, since the inside of the set has no slope.
+            Self::Atom | Self::Misiurewicz => return color,
+            _ => color,
+        };
+
+        match lighting {
+            Some(lighting) => lighting.shade(color, pixel.slope),
+            None => color,
+        }
+    }
+}
+
+/// The continuous iteration count of a pixel which escaped, which is between `iters` and `iters + 1` depending on how far past the bailout z got.
+///
+/// `log_degree` is ln n for a formula of degree n (see `Formula::log_degree`); anything else leaves bands where `iters` changes.
+pub fn smooth_iterations(pixel: &Pixel, bailout: f32, log_degree: f32) -> f32 {
+    // log|z| / log(bailout), which is between 1 and n since z can only get up to about bailoutⁿ in the last iteration.
+    let ratio = pixel.norm.ln() / (bailout * bailout).ln();
+    pixel.iters as f32 + 1.0 - ratio.max(1.0).ln() / log_degree
+}
+
+/// How much to darken a pixel whose final z has an argument of `angle` with `Coloring::FieldLines`,
+/// from 0 on a line to 1 away from them.
+pub fn field_line_shade(angle: f32) -> f32 {
+    let turns = angle / TAU * FIELD_LINES;
+    // The distance to the nearest line, from 0 on it to 1 halfway between two of them.
+    let offset = (turns - turns.round()).abs() * 2.0;
+    let t = (offset / FIELD_LINE_WIDTH).clamp(0.0, 1.0);
+    // The same as WGSL's `smoothStep`.
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Estimates how far a point which escaped with |z|² = `norm` is from the set, given |dz/dc|.
+///
+/// This is the lower bound |z| ln|z| / 2|dz/dc|, which is within a factor of 4 of the real distance,
+/// and comes out in whatever units dz/dc's c is measured in.
+pub fn distance_estimate(norm: f32, derivative: f32) -> f32 {
+    // |z| ln|z| = sqrt(|z|²) ln(|z|²) / 2
+    0.25 * norm.sqrt() * norm.ln() / derivative
+}
+
+/// Gets the gradient of the smooth iteration count of a point which escaped at `z`, given dz/dc measured in pixels.
+///
+/// The smooth iteration count is n + 1 - logₖ(ln|z| / ln(bailout)) for a formula of degree k, so its gradient is -∇ln|z| / (ln k ln|z|),
+/// and ∇ln|z| = (Re w, -Im w) where w = (dz/dc) / z. `log_degree` is ln k.
+pub fn smooth_slope(z: [f32; 2], derivative: [f32; 2], log_degree: f32) -> [f32; 2] {
+    let [x, y] = z;
+    let [dx, dy] = derivative;
+    let norm = x * x + y * y;
+    let w = [(dx * x + dy * y) / norm, (dy * x - dx * y) / norm];
+    let scale = -1.0 / (log_degree * 0.5 * norm.ln());
+    [w[0] * scale, -w[1] * scale]
+}
+
+/// A directional light which treats the smooth iteration count as a height field, giving the image an embossed look.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lighting {
+    /// The direction the light comes from, in radians anticlockwise from the positive real axis.
+    pub angle: f32,
+    /// How far above the image the light is, in radians.
+    pub elevation: f32,
+    /// How bright the parts facing away from the light are, from 0 to 1.
+    pub ambient: f32,
+    /// How bright the highlights are.
+    pub specular: f32,
+    /// How much the height field gets stretched vertically, which makes its slopes steeper.
+    pub depth: f32,
+}
+
+impl Default for Lighting {
+    /// A light from the top right, halfway up the sky.
+    fn default() -> Self {
+        Self {
+            angle: FRAC_PI_4,
+            elevation: FRAC_PI_4,
+            ambient: 0.2,
+            specular: 0.3,
+            depth: 1.0,
+        }
+    }
+}
+
+impl Lighting {
+    /// Lights `color` as if the smooth iteration count were a height field whose gradient is `slope`.
+    pub fn shade(&self, color: [f32; 3], slope: [f32; 2]) -> [f32; 3] {
+        // Clamp the gradient so that ones which overflowed still give a valid (if nearly vertical) normal.
+        let limit = 1e6;
+        let gradient = slope.map(|component| (component * self.depth).clamp(-limit, limit));
+        let normal = normalize([-gradient[0], -gradient[1], 1.0]);
+
+        let light = [
+            self.elevation.cos() * self.angle.cos(),
+            self.elevation.cos() * self.angle.sin(),
+            self.elevation.sin(),
+        ];
+        let diffuse = dot(normal, light).max(0.0);
+
+        // Blinn-Phong highlights, with the image being viewed from straight above.
+        let halfway = normalize([light[0], light[1], light[2] + 1.0]);
+        let highlight = self.specular * dot(normal, halfway).max(0.0).powf(SHININESS);
+
+        color.map(|component| {
+            component * (self.ambient + (1.0 - self.ambient) * diffuse) + highlight
+        })
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = dot(v, v).sqrt();
+    v.map(|component| component / len)
+}
+
+/// The cumulative histogram of escaped pixels' smooth iteration counts, which `histogram.wgsl` builds on the GPU.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// The number of escaped pixels in each bin or any before it.
+    pub bins: Vec<u32>,
+}
+
+impl Histogram {
+    /// Builds the histogram of `pixels`, which were iterated with a limit of `iterations` and a bailout radius of `bailout`,
+    /// by a formula whose `Formula::log_degree` is `log_degree`.
+    pub fn new(pixels: &[Pixel], iterations: u32, bailout: f32, log_degree: f32) -> Self {
+        let mut bins = vec![0; HISTOGRAM_BINS as usize];
+        for pixel in pixels.iter().filter(|pixel| pixel.iters < iterations) {
+            let value = smooth_iterations(pixel, bailout, log_degree) / iterations as f32;
+            bins[histogram_bin(value) as usize] += 1;
+        }
+
+        let mut total = 0;
+        for bin in &mut bins {
+            total += *bin;
+            *bin = total;
+        }
+
+        Self { bins }
+    }
+
+    /// Maps `value` through the histogram, so that pixels end up spread evenly between 0 and 1.
+    pub fn equalize(&self, value: f32) -> f32 {
+        let total = self.bins[HISTOGRAM_BINS as usize - 1];
+        if total == 0 {
+            return value;
+        }
+
+        // Blend between the bins' edges, so that the bands between them don't show.
+        let bin = histogram_bin(value) as usize;
+        let below = if bin > 0 { self.bins[bin - 1] } else { 0 };
+        let fraction = (value * HISTOGRAM_BINS as f32 - bin as f32).clamp(0.0, 1.0);
+        (below as f32 + fraction * (self.bins[bin] - below) as f32) / total as f32
+    }
+}
+
+/// Which bin of the histogram a pixel with a colouring value of `value` goes in.
+fn histogram_bin(value: f32) -> u32 {
+    ((value.max(0.0) * HISTOGRAM_BINS as f32) as u32).min(HISTOGRAM_BINS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formula::Formula;
+    use std::f32::consts::LN_2;
+
+    /// A pixel which escaped after `iters` iterations with |z|² = `norm`.
+    fn escaped(iters: u32, norm: f32) -> Pixel {
+        Pixel {
+            iters,
+            norm,
+            ..Pixel::default()
+        }
+    }
+
+    #[test]
+    fn smooth_iterations_known_values() {
+        let bailout = DEFAULT_BAILOUT;
+        // Landing right on the bailout counts as a whole extra iteration, and landing on its square as none.
+        assert!(
+            (smooth_iterations(&escaped(10, bailout * bailout), bailout, LN_2) - 11.0).abs() < 1e-5
+        );
+        assert!(
+            (smooth_iterations(&escaped(10, bailout.powi(4)), bailout, LN_2) - 10.0).abs() < 1e-4
+        );
+        // log2(ln|z| / ln(bailout)) = log2(1.5)
+        let norm = bailout.powi(3);
+        let expected = 11.0 - 1.5f32.log2();
+        assert!((smooth_iterations(&escaped(10, norm), bailout, LN_2) - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn smooth_coloring_is_continuous() {
+        let iterations = 1000;
+        let bailout = DEFAULT_BAILOUT;
+        let value = |coloring: Coloring, c: f64| {
+            let pixel = Formula::MANDELBROT.iterate([c, 0.0], None, iterations, bailout, 1.0);
+            coloring
+                .value(&pixel, iterations, bailout, LN_2, None)
+                .unwrap()
+                * iterations as f32
+        };
+
+        // Walking along the real axis past the cusp, the discrete count jumps by whole iterations but the smooth one barely moves.
+        let mut discrete_jumps = 0;
+        for i in 0..2000 {
+            let c = 0.3 + i as f64 * 1e-4;
+            let next = c + 1e-4;
+            if value(Coloring::Discrete, c) != value(Coloring::Discrete, next) {
+                discrete_jumps += 1;
+            }
+            let step = (value(Coloring::Smooth, c) - value(Coloring::Smooth, next)).abs();
+            assert!(step < 0.05, "smooth count jumped by {} at {}", step, c);
+        }
+        assert!(discrete_jumps > 0);
+    }
+
+    #[test]
+    fn smooth_coloring_is_continuous_for_higher_powers() {
+        let formula = Formula::multibrot(3).unwrap();
+        let iterations = 1000;
+        let bailout = DEFAULT_BAILOUT;
+        let value = |c: f64, log_degree: f32| {
+            let pixel = formula.iterate([c, 0.0], None, iterations, bailout, 1.0);
+            smooth_iterations(&pixel, bailout, log_degree)
+        };
+        let max_step = |log_degree: f32| {
+            (0..2000)
+                .map(|i| {
+                    let c = 0.45 + i as f64 * 1e-4;
+                    (value(c, log_degree) - value(c + 1e-4, log_degree)).abs()
+                })
+                .fold(0.0, f32::max)
+        };
+
+        // Taking the logarithm to base 2 instead of 3 leaves a jump of about log2(3) - 1 wherever the discrete count changes.
+        assert!(max_step(formula.log_degree()) < 0.05);
+        assert!(max_step(LN_2) > 0.3);
+    }
+
+    #[test]
+    fn in_set_has_no_value() {
+        let pixel = Formula::MANDELBROT.iterate([-0.5, 0.0], None, 100, DEFAULT_BAILOUT, 1.0);
+        assert_eq!(pixel.iters, 100);
+        assert_eq!(
+            Coloring::Smooth.value(&pixel, 100, DEFAULT_BAILOUT, LN_2, None),
+            None
+        );
+    }
+
+    #[test]
+    fn histogram_is_cumulative() {
+        let iterations = 100;
+        let mut pixels: Vec<Pixel> = (0..50).map(|i| escaped(i, 1e6)).collect();
+        // Pixels in the set get left out.
+        pixels.push(escaped(iterations, 1.0));
+
+        let histogram = Histogram::new(&pixels, iterations, DEFAULT_BAILOUT, LN_2);
+        assert_eq!(histogram.bins.len(), HISTOGRAM_BINS as usize);
+        assert!(histogram.bins.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(*histogram.bins.last().unwrap(), 50);
+    }
+
+    #[test]
+    fn histogram_spreads_values_evenly() {
+        let iterations = 1000;
+        // Most of the pixels are bunched up at the start of the range, like at a deep zoom.
+        let pixels: Vec<Pixel> = (0..1000).map(|i| escaped(10 + i / 20, 1e6)).collect();
+        let histogram = Histogram::new(&pixels, iterations, DEFAULT_BAILOUT, LN_2);
+
+        let mut equalized: Vec<f32> = pixels
+            .iter()
+            .map(|pixel| {
+                Coloring::Histogram
+                    .value(pixel, iterations, DEFAULT_BAILOUT, LN_2, Some(&histogram))
+                    .unwrap()
+            })
+            .collect();
+        equalized.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        assert!(equalized.iter().all(|value| (0.0..=1.0).contains(value)));
+        // Without equalization these would all be below 0.07; with it, they're spread across the whole palette.
+        let median = equalized[equalized.len() / 2];
+        assert!((median - 0.5).abs() < 0.05, "median was {}", median);
+        assert!(*equalized.last().unwrap() > 0.95);
+    }
+
+    #[test]
+    fn equalize_is_monotonic() {
+        let pixels: Vec<Pixel> = (0..200).map(|i| escaped(i % 37, 1e5)).collect();
+        let histogram = Histogram::new(&pixels, 100, DEFAULT_BAILOUT, LN_2);
+        let values: Vec<f32> = (0..=1000)
+            .map(|i| histogram.equalize(i as f32 / 1000.0))
+            .collect();
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(values[1000], 1.0);
+    }
+
+    /// The estimated distance from `c` to the Mandelbrot set, on the complex plane.
+    fn distance_to_set(c: [f64; 2]) -> f32 {
+        let pixel = Formula::MANDELBROT.iterate(c, None, 100_000, MAX_BAILOUT, 1.0);
+        assert!(pixel.iters < 100_000, "{:?} didn't escape", c);
+        pixel.distance
+    }
+
+    #[test]
+    fn distance_estimate_near_the_tip() {
+        // Straight out from the tip at -2, the closest point of the set is the tip itself.
+        for &distance in &[0.5, 0.01, 0.001] {
+            // It's a lower bound, which is within a factor of 4 of the real distance.
+            let estimate = distance_to_set([-2.0 - distance, 0.0]);
+            assert!(
+                estimate <= distance as f32 && estimate >= distance as f32 / 4.0,
+                "estimated {} for a point {} from the tip",
+                estimate,
+                distance
+            );
+        }
+    }
+
+    #[test]
+    fn distance_estimate_is_a_lower_bound_at_the_cusp() {
+        // Next to the cusp at 1/4 escaping slows right down, which makes the estimate much too cautious, but it still mustn't overshoot.
+        for &distance in &[0.01, 0.0001] {
+            let estimate = distance_to_set([0.25 + distance, 0.0]);
+            assert!(estimate > 0.0 && estimate <= distance as f32);
+        }
+    }
+
+    #[test]
+    fn distance_is_in_pixels() {
+        let c = [-2.5, 0.0];
+        let pixel_size = 1e-3;
+        let pixel = Formula::MANDELBROT.iterate(c, None, 1000, DEFAULT_BAILOUT, pixel_size);
+        let plane = Formula::MANDELBROT.iterate(c, None, 1000, DEFAULT_BAILOUT, 1.0);
+        assert!(
+            (pixel.distance * pixel_size as f32 - plane.distance).abs() < 1e-4 * plane.distance
+        );
+    }
+}
@@ -0,0 +1,383 @@
+// Turns the results of iterating each pixel into colours.
+// `accumulate_main` adds each pass's colours up in the accumulation buffer, and `fs_main` averages them onto the screen.
+
+// The most layers there can be, and the number of texels in each of their palettes.
+// These have to match `MAX_LAYERS` in `layer.rs` and `TEXTURE_SIZE` in `palette.rs`.
+let MAX_LAYERS: u32 = 8u;
+let PALETTE_TEXTURE_SIZE: f32 = 1024.0;
+
+// How layers' palettes handle positions outside of 0 to 1 (see `Wrap` in `palette.rs`).
+let WRAP_CLAMP: u32 = 0u;
+let WRAP_REPEAT: u32 = 1u;
+let WRAP_MIRROR: u32 = 2u;
+
+// How layers get blended onto the ones below them (see `Blend` in `layer.rs`).
+let BLEND_NORMAL: u32 = 0u;
+let BLEND_MULTIPLY: u32 = 1u;
+let BLEND_SCREEN: u32 = 2u;
+let BLEND_OVERLAY: u32 = 3u;
+
+// The size of the Bayer matrix used for ordered dithering.
+// This has to match `DITHER_SIZE` in `output.rs`.
+let DITHER_SIZE: u32 = 8u;
+
+[[block]]
+struct Histogram {
+    // The number of escaped pixels in each bin or any before it.
+    bins: array<u32, HISTOGRAM_BINS>;
+};
+
+// The settings for a light (see `Lighting` in `coloring.rs`).
+struct Light {
+    angle: f32;
+    elevation: f32;
+    ambient: f32;
+    specular: f32;
+    depth: f32;
+};
+
+// A colouring composited over the ones below it (see `Layer` in `layer.rs`).
+struct Layer {
+    // One of the `COLORING_*` constants.
+    coloring: u32;
+    // One of the `BLEND_*` constants.
+    blend: u32;
+    // One of the `WRAP_*` constants.
+    wrap: u32;
+    // Whether `light` is used.
+    lighting: u32;
+    opacity: f32;
+    palette_offset: f32;
+    palette_scale: f32;
+    light: Light;
+};
+
+[[block]]
+struct Layers {
+    count: u32;
+    layers: [[stride(48)]] array<Layer, MAX_LAYERS>;
+};
+
+[[block]]
+struct Accumulation {
+    // The sum of each pixel's samples' linear RGB colours, with the number of samples in the alpha channel.
+    samples: array<vec4<f32>>;
+};
+
+[[group(0), binding(1)]] var<storage, read> pixels: Pixels;
+[[group(0), binding(2)]] var<storage, read> histogram: Histogram;
+[[group(0), binding(3)]] var<storage, read> layers: Layers;
+[[group(0), binding(4)]] var<storage, read_write> accumulation: Accumulation;
+// The palette baked into a texture, and a sampler which takes care of wrapping positions outside of 0 to 1 (see `palette.rs`).
+[[group(0), binding(5)]] var palette: texture_1d<f32>;
+[[group(0), binding(6)]] var palette_sampler: sampler;
+// The same for the palette pixels in the set are coloured with.
+[[group(0), binding(7)]] var interior_palette: texture_1d<f32>;
+[[group(0), binding(8)]] var interior_palette_sampler: sampler;
+// Every layer's palette, one per row, whose wrapping is done manually since it's different for each.
+[[group(0), binding(9)]] var layer_palettes: texture_2d<f32>;
+[[group(0), binding(10)]] var layer_palette_sampler: sampler;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] idx: u32) -> [[builtin(position)]] vec4<f32> {
+    // Return the four points necessary to cover the entire screen with a triangle strip (forming a rectangle).
+    if (idx == 0u) {
+        return vec4<f32>(-1.0, 1.0, 0.0, 1.0);
+    } elseif (idx == 1u) {
+        return vec4<f32>(1.0, 1.0, 0.0, 1.0);
+    } elseif (idx == 2u) {
+        return vec4<f32>(-1.0, -1.0, 0.0, 1.0);
+    } else {
+        return vec4<f32>(1.0, -1.0, 0.0, 1.0);
+    }
+}
+
+// Maps `value` through the cumulative histogram, so that pixels end up spread evenly between 0 and 1.
+// This has to match `Histogram::equalize` in `coloring.rs`.
+fn equalize(value: f32) -> f32 {
+    let total = histogram.bins[HISTOGRAM_BINS - 1u];
+    if (total == 0u) {
+        return value;
+    }
+
+    // Blend between the bins' edges, so that the bands between them don't show.
+    let bin = histogram_bin(value);
+    var below = 0u;
+    if (bin > 0u) {
+        below = histogram.bins[bin - 1u];
+    }
+    let fraction = clamp(value * f32(HISTOGRAM_BINS) - f32(bin), 0.0, 1.0);
+    return (f32(below) + fraction * f32(histogram.bins[bin] - below)) / f32(total);
+}
+
+// The number of field lines drawn per turn around the set, and how wide they are as a fraction of the gap between them.
+// These have to match `FIELD_LINES` and `FIELD_LINE_WIDTH` in `coloring.rs`.
+let FIELD_LINES: f32 = 16.0;
+let FIELD_LINE_WIDTH: f32 = 0.2;
+
+// How much to darken a pixel whose final z has an argument of `angle` for field line colouring, from 0 on a line to 1 away from them.
+// This has to match `field_line_shade` in `coloring.rs`.
+fn field_line_shade(angle: f32) -> f32 {
+    let turns = angle / 6.2831853 * FIELD_LINES;
+    // The distance to the nearest line, from 0 on it to 1 halfway between two of them.
+    let offset = abs(turns - round(turns)) * 2.0;
+    return smoothStep(0.0, FIELD_LINE_WIDTH, offset);
+}
+
+// How many domains atom and Misiurewicz domain colouring fit into the palette before it starts repeating.
+// This has to match `DOMAIN_PALETTE_PERIODS` in `coloring.rs`.
+let DOMAIN_PALETTE_PERIODS: f32 = 12.0;
+
+// How many periods interior period colouring fits into the palette, and how many doublings of the distance interior distance colouring does.
+// These have to match `PALETTE_PERIODS` and `PALETTE_DISTANCE_OCTAVES` in `interior.rs`.
+let INTERIOR_PALETTE_PERIODS: f32 = 12.0;
+let INTERIOR_PALETTE_DISTANCE_OCTAVES: f32 = 16.0;
+
+// Colours a pixel in the set according to `settings.interior_coloring`.
+// This has to match `InteriorColoring::color` in `interior.rs`.
+fn interior_color(pixel: Pixel) -> vec3<f32> {
+    var value = 0.0;
+    if (settings.interior_coloring == INTERIOR_NONE) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    } elseif (settings.interior_coloring == INTERIOR_MAGNITUDE) {
+        value = sqrt(pixel.norm) / 2.0;
+    } elseif (pixel.period == 0u) {
+        return vec3<f32>(0.0, 0.0, 0.0);
+    } elseif (settings.interior_coloring == INTERIOR_PERIOD) {
+        value = f32(pixel.period - 1u) / INTERIOR_PALETTE_PERIODS;
+    } elseif (settings.interior_coloring == INTERIOR_DISTANCE) {
+        value = log2(1.0 + pixel.distance) / INTERIOR_PALETTE_DISTANCE_OCTAVES;
+    } else {
+        value = length(pixel.multiplier);
+    }
+
+    let position = value * settings.interior_palette_scale + settings.interior_palette_offset;
+    return textureSampleLevel(interior_palette, interior_palette_sampler, position, 0.0).rgb;
+}
+
+// How tightly focused the highlights from the light are.
+let SHININESS: f32 = 32.0;
+
+// Lights `color` as if the smooth iteration count were a height field whose gradient is `slope`.
+// This has to match `Lighting::shade` in `coloring.rs`.
+fn light(color: vec3<f32>, slope: vec2<f32>, light: Light) -> vec3<f32> {
+    // Clamp the gradient so that ones which overflowed still give a valid (if nearly vertical) normal.
+    let limit = 1000000.0;
+    let gradient = clamp(slope * light.depth, vec2<f32>(-limit, -limit), vec2<f32>(limit, limit));
+    let normal = normalize(vec3<f32>(-gradient, 1.0));
+
+    let direction = vec3<f32>(
+        cos(light.elevation) * cos(light.angle),
+        cos(light.elevation) * sin(light.angle),
+        sin(light.elevation),
+    );
+    let diffuse = max(dot(normal, direction), 0.0);
+
+    // Blinn-Phong highlights, with the image being viewed from straight above.
+    let halfway = normalize(direction + vec3<f32>(0.0, 0.0, 1.0));
+    let highlight = light.specular * pow(max(dot(normal, halfway), 0.0), SHININESS);
+
+    return color * (light.ambient + (1.0 - light.ambient) * diffuse) + vec3<f32>(highlight, highlight, highlight);
+}
+
+fn is_domain(coloring: u32) -> bool {
+    return coloring == COLORING_ATOM || coloring == COLORING_MISIUREWICZ;
+}
+
+// Where `pixel` lands in the palette with `coloring` before its offset and scale are applied,
+// assuming it escaped or `coloring` is a domain colouring.
+// This has to match `Coloring::value` in `coloring.rs`.
+fn coloring_value(pixel: Pixel, coloring: u32) -> f32 {
+    if (coloring == COLORING_ATOM) {
+        return f32(max(pixel.atom, 1u) - 1u) / DOMAIN_PALETTE_PERIODS;
+    } elseif (coloring == COLORING_MISIUREWICZ) {
+        return f32(max(pixel.misiurewicz, 1u) - 1u) / DOMAIN_PALETTE_PERIODS;
+    } elseif (coloring == COLORING_TRAP) {
+        return pixel.trap;
+    } elseif (coloring == COLORING_STRIPES) {
+        return pixel.stripe;
+    }
+
+    var iters = f32(pixel.iters);
+    if (coloring != COLORING_DISCRETE) {
+        iters = smooth_iterations(pixel);
+    }
+
+    let value = iters / f32(settings.iterations);
+    if (coloring == COLORING_HISTOGRAM) {
+        return equalize(value);
+    }
+    return value;
+}
+
+// Applies whatever `coloring` does to the colour it got from the palette, and then lights it if `lighting` is set.
+// This has to match the rest of `Coloring::color` in `coloring.rs`.
+fn coloring_shade(color_in: vec3<f32>, pixel: Pixel, coloring: u32, lighting: bool, light_settings: Light) -> vec3<f32> {
+    var color = color_in;
+    if (is_domain(coloring)) {
+        // Domains aren't lit, since the inside of the set has no slope.
+        return color;
+    } elseif (coloring == COLORING_DISTANCE) {
+        // Fade to black over the last pixel before the set, so that filaments get drawn even where they miss every pixel's center.
+        color = color * clamp(pixel.distance, 0.0, 1.0);
+    } elseif (coloring == COLORING_BINARY && pixel.angle < 0.0) {
+        color = vec3<f32>(0.0, 0.0, 0.0);
+    } elseif (coloring == COLORING_FIELD_LINES) {
+        color = color * field_line_shade(pixel.angle);
+    }
+
+    if (lighting) {
+        color = light(color, pixel.slope, light_settings);
+    }
+    return color;
+}
+
+// Samples row `layer` of `layer_palettes` at `position`, wrapping it like `Palette::color` in `palette.rs`.
+fn sample_layer_palette(layer: u32, position: f32, wrap: u32) -> vec3<f32> {
+    var wrapped = clamp(position, 0.0, 1.0);
+    if (wrap == WRAP_REPEAT) {
+        wrapped = position - floor(position);
+    } elseif (wrap == WRAP_MIRROR) {
+        wrapped = 1.0 - abs(position - 2.0 * floor(position / 2.0) - 1.0);
+    }
+
+    // Stay between the centres of the end texels, so that the filtering doesn't blend the two ends together.
+    let half_texel = 0.5 / PALETTE_TEXTURE_SIZE;
+    let u = clamp(wrapped, half_texel, 1.0 - half_texel);
+    let v = (f32(layer) + 0.5) / f32(MAX_LAYERS);
+    return textureSampleLevel(layer_palettes, layer_palette_sampler, vec2<f32>(u, v), 0.0).rgb;
+}
+
+// Blends `above` onto `below` with one of the `BLEND_*` modes.
+// This has to match `Blend::apply` in `layer.rs`.
+fn blend(below: vec3<f32>, above: vec3<f32>, mode: u32) -> vec3<f32> {
+    if (mode == BLEND_MULTIPLY) {
+        return below * above;
+    } elseif (mode == BLEND_SCREEN) {
+        return 1.0 - (1.0 - below) * (1.0 - above);
+    } elseif (mode == BLEND_OVERLAY) {
+        let dark = 2.0 * below * above;
+        let light = 1.0 - 2.0 * (1.0 - below) * (1.0 - above);
+        return select(light, dark, below < vec3<f32>(0.5, 0.5, 0.5));
+    }
+    return above;
+}
+
+// Converts linear RGB to sRGB.
+// This has to match `linear_to_srgb` in `palette.rs`.
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let curve = 1.055 * pow(color, vec3<f32>(1.0 / 2.4, 1.0 / 2.4, 1.0 / 2.4)) - 0.055;
+    return select(curve, color * 12.92, color <= vec3<f32>(0.0031308, 0.0031308, 0.0031308));
+}
+
+// The inverse of `linear_to_srgb`.
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let curve = pow((color + 0.055) / 1.055, vec3<f32>(2.4, 2.4, 2.4));
+    return select(curve, color / 12.92, color <= vec3<f32>(0.04045, 0.04045, 0.04045));
+}
+
+// The ordered dithering threshold for the pixel at `position`, from -0.5 to 0.5.
+// This has to match `dither_threshold` in `output.rs`.
+fn bayer(position: vec2<u32>) -> f32 {
+    // The bits of the matrix's index are those of x ^ y and y interleaved, with the lowest ones first.
+    let x = position.x % DITHER_SIZE;
+    let y = position.y % DITHER_SIZE;
+    let xy = x ^ y;
+    var index = 0u;
+    for (var bit = 1u; bit < DITHER_SIZE; bit = bit * 2u) {
+        index = (index << 2u) | (select(0u, 2u, (xy & bit) != 0u)) | select(0u, 1u, (y & bit) != 0u);
+    }
+    return (f32(index) + 0.5) / f32(DITHER_SIZE * DITHER_SIZE) - 0.5;
+}
+
+// Gets the linear RGB colour of `pixel`, with all the layers composited on top.
+fn pixel_color(pixel: Pixel) -> vec3<f32> {
+    // Glitches no secondary reference fixed (which is all of them on the web, where they can't be read back) get flagged
+    // rather than passed off as part of the set, in a colour which stands out against any palette.
+    // This has to match `GLITCH_COLOR` in `perturbation.rs`.
+    if ((pixel.flags & GLITCHED) != 0u) {
+        return vec3<f32>(1.0, 0.0, 1.0);
+    }
+
+    let escaped = pixel.iters < settings.iterations;
+
+    // The bottom layer comes from the main colouring settings, and the inside of the set gets the interior colouring.
+    var color = interior_color(pixel);
+    if (escaped || is_domain(settings.coloring)) {
+        let position = coloring_value(pixel, settings.coloring) * settings.palette_scale + settings.palette_offset;
+        let light_settings = Light(settings.light_angle, settings.light_elevation, settings.ambient, settings.specular, settings.slope_depth);
+        color = coloring_shade(
+            textureSampleLevel(palette, palette_sampler, position, 0.0).rgb,
+            pixel,
+            settings.coloring,
+            settings.lighting != 0u,
+            light_settings,
+        );
+    }
+
+    // Everything else gets composited on top, leaving the inside of the set alone unless they're domain colourings.
+    for (var i = 0u; i < layers.count; i = i + 1u) {
+        let layer = layers.layers[i];
+        if (!escaped && !is_domain(layer.coloring)) {
+            continue;
+        }
+
+        let position = coloring_value(pixel, layer.coloring) * layer.palette_scale + layer.palette_offset;
+        let above = coloring_shade(
+            sample_layer_palette(i, position, layer.wrap),
+            pixel,
+            layer.coloring,
+            layer.lighting != 0u,
+            layer.light,
+        );
+        color = mix(color, blend(color, above, layer.blend), vec3<f32>(layer.opacity, layer.opacity, layer.opacity));
+    }
+
+    return color;
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn accumulate_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= settings.width || id.y >= settings.height) {
+        return;
+    }
+
+    let index = id.y * settings.width + id.x;
+    let pixel = pixels.pixels[index];
+    let edge = (pixel.flags & EDGE) != 0u;
+    if (settings.sample_pixels == SAMPLE_EDGES && !edge) {
+        return;
+    }
+
+    // The edges' centres get left out, since they're about to be supersampled.
+    var weight = 1.0;
+    if (settings.sample_pixels == SAMPLE_SMOOTH && edge) {
+        weight = 0.0;
+    }
+    var sample = vec4<f32>(pixel_color(pixel) * weight, weight);
+    if (settings.accumulate != 0u) {
+        sample = sample + accumulation.samples[index];
+    }
+    accumulation.samples[index] = sample;
+}
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let coordinates = vec2<u32>(u32(position.x), u32(position.y)) - settings.origin;
+    let sum = accumulation.samples[coordinates.y * settings.width + coordinates.x];
+    // Colours are averaged in linear RGB, so that edges come out as bright as they really are.
+    let color = sum.rgb / max(sum.a, 1.0);
+
+    // Everything up to here has been in linear RGB, so all that's left is encoding it as sRGB for the output,
+    // which gets dithered before it's rounded to hide the banding.
+    var encoded = linear_to_srgb(clamp(color, vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0)));
+    if (settings.dither_step > 0.0) {
+        let offset = bayer(coordinates) * settings.dither_step;
+        encoded = clamp(encoded + offset, vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0));
+    }
+    if (settings.encode_srgb == 0u) {
+        // The swapchain is going to encode it as sRGB itself, so undo it.
+        return vec4<f32>(srgb_to_linear(encoded), 1.0);
+    }
+    return vec4<f32>(encoded, 1.0);
+}
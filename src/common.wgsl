@@ -0,0 +1,409 @@
+// Declarations shared between the iteration and colorizing shaders.
+// This gets prepended to each of them before they're compiled.
+
+[[block]]
+struct Settings {
+    center: vec2<f32>;
+    // The position of the camera, as the high and low halves of a double-single number (see `doublesingle.wgsl`).
+    camera: vec2<f32>;
+    camera_low: vec2<f32>;
+    // The offset of the current reference orbit's point from the camera, in pixels.
+    reference_offset: vec2<f32>;
+
+    // The mantissas of the coefficients of the series approximation after `series_skip` iterations.
+    series_a: vec2<f32>;
+    series_b: vec2<f32>;
+    series_c: vec2<f32>;
+
+    width: u32;
+    height: u32;
+
+    iterations: u32;
+    // The distance between pixels on the complex plane (1 / zoom), split into a mantissa and exponent so that it never underflows.
+    pixel_size: f32;
+    pixel_size_exponent: i32;
+
+    // The number of values in the reference orbit.
+    reference_len: u32;
+    // Whether this pass is re-rendering glitched pixels from a secondary reference, rather than rendering every pixel.
+    secondary: u32;
+
+    // The number of iterations skipped by series approximation, and the exponents of its coefficients.
+    series_skip: u32;
+    series_a_exponent: i32;
+    series_b_exponent: i32;
+    series_c_exponent: i32;
+
+    // The number of levels in the BLA table.
+    bla_levels: u32;
+
+    // The number of iterations after which escaping counts as close to the limit (see `iterations.rs`).
+    near_limit: u32;
+    // How close z has to come to an earlier value for a pixel to count as periodic, or 0 if periodicity checking is disabled.
+    period_tolerance: f32;
+
+    // How far z has to get from the origin to count as escaped.
+    bailout: f32;
+    // How pixels are coloured: one of the `COLORING_*` constants.
+    coloring: u32;
+
+    // Where pixels land in the palette is `value * palette_scale + palette_offset`, where `value` comes from the colouring mode.
+    palette_offset: f32;
+    palette_scale: f32;
+
+    // Whether to light the image like a height field (see `Lighting` in `coloring.rs`), and the light's settings.
+    lighting: u32;
+    light_angle: f32;
+    light_elevation: f32;
+    ambient: f32;
+    specular: f32;
+    slope_depth: f32;
+
+    // The orbit trap's geometry (see `OrbitTrap` in `orbit.rs`), with the shape being one of the `TRAP_*` constants.
+    trap_center: vec2<f32>;
+    trap_shape: u32;
+    trap_angle: f32;
+    trap_radius: f32;
+    // How many stripes there are per turn around the origin for stripe average colouring.
+    stripe_frequency: f32;
+
+    // How pixels in the set are coloured: one of the `INTERIOR_*` constants.
+    interior_coloring: u32;
+    interior_palette_offset: f32;
+    interior_palette_scale: f32;
+
+    // Which values the iteration shaders need to build up along each orbit for the colouring and its layers: some of the `TRACK_*` flags.
+    orbit_tracking: u32;
+
+    // Whether `fs_main` has to encode its output as sRGB itself, because the swapchain's format doesn't (see `output.rs`).
+    encode_srgb: u32;
+    // The size of the steps the output gets rounded to, from 0 to 1, for ordered dithering; or 0 to not dither.
+    dither_step: f32;
+
+    // The offset of the point iterated from the centre of each pixel in this pass, in pixels (see `supersampling.rs`).
+    sample_offset: vec2<f32>;
+    // Which pixels this pass iterates and adds to the accumulation buffer: one of the `SAMPLE_*` constants.
+    sample_pixels: u32;
+    // Whether this pass adds to what's already in the accumulation buffer, rather than replacing it.
+    accumulate: u32;
+    // How many iterations apart a pixel's neighbours have to be for it to count as an edge with adaptive supersampling.
+    edge_threshold: u32;
+
+    // Whether a Julia set is being rendered, where each pixel is z₀ rather than c and c is `julia_c` for all of them.
+    julia: u32;
+    // The Julia set's c, as the high and low halves of a double-single number.
+    julia_c: vec2<f32>;
+    julia_c_low: vec2<f32>;
+
+    // The position of the viewport's top-left corner in the window, which `fs_main` subtracts to find which pixel it's drawing (see `viewport.rs`).
+    origin: vec2<u32>;
+
+    // ln of the formula's degree, which is the base smooth colouring takes its logarithm to (see `Formula::log_degree`).
+    log_degree: f32;
+};
+
+// Set on pixels which need to be re-rendered from a different reference orbit.
+let GLITCHED: u32 = 1u;
+// Set on pixels whose neighbours' iteration counts are far enough apart that adaptive supersampling supersamples them.
+let EDGE: u32 = 2u;
+
+// Which pixels a pass iterates and adds to the accumulation buffer (see `SamplePixels` in `supersampling.rs`).
+let SAMPLE_ALL: u32 = 0u;
+let SAMPLE_SMOOTH: u32 = 1u;
+let SAMPLE_EDGES: u32 = 2u;
+
+// Colour pixels by how many iterations they took to escape.
+let COLORING_DISCRETE: u32 = 0u;
+// Colour pixels by a continuous version of the iteration count, which takes into account how far past the bailout they ended up.
+let COLORING_SMOOTH: u32 = 1u;
+// Like `COLORING_SMOOTH`, but evening out how much of the palette is used by mapping the iteration count through its histogram.
+let COLORING_HISTOGRAM: u32 = 2u;
+// Like `COLORING_SMOOTH`, but shading pixels darker the closer they are to the set, so that filaments thinner than a pixel still show up.
+let COLORING_DISTANCE: u32 = 3u;
+// Colour pixels by how close their orbits got to the orbit trap.
+let COLORING_TRAP: u32 = 4u;
+// Colour pixels by the average of sin(stripe_frequency × arg z) over their orbits.
+let COLORING_STRIPES: u32 = 5u;
+// Like `COLORING_SMOOTH`, but blacking out pixels whose final z has a negative imaginary part.
+let COLORING_BINARY: u32 = 6u;
+// Like `COLORING_SMOOTH`, but drawing dark lines along the external rays at evenly spaced angles.
+let COLORING_FIELD_LINES: u32 = 7u;
+// Colour every pixel by its atom domain: the iteration where |z| was smallest.
+let COLORING_ATOM: u32 = 8u;
+// Colour every pixel by its Misiurewicz domain: the iteration q where |z_{q+1} - z_q| was smallest.
+let COLORING_MISIUREWICZ: u32 = 9u;
+
+// Leave pixels in the set black.
+let INTERIOR_NONE: u32 = 0u;
+// Colour pixels in the set by |z| at the end of their orbits, or of their attracting cycles if their periods are known.
+let INTERIOR_MAGNITUDE: u32 = 1u;
+// Colour pixels in the set by the periods of their attracting cycles.
+let INTERIOR_PERIOD: u32 = 2u;
+// Colour pixels in the set by their estimated distance to the edge of their hyperbolic components.
+let INTERIOR_DISTANCE: u32 = 3u;
+// Colour pixels in the set by the magnitude of their attracting cycles' multipliers.
+let INTERIOR_MULTIPLIER: u32 = 4u;
+
+// The flags for `orbit_tracking`; these have to match the ones in `orbit.rs`.
+let TRACK_TRAP: u32 = 1u;
+let TRACK_STRIPES: u32 = 2u;
+let TRACK_ATOM: u32 = 4u;
+let TRACK_MISIUREWICZ: u32 = 8u;
+
+let TRAP_POINT: u32 = 1u;
+let TRAP_LINE: u32 = 2u;
+let TRAP_CROSS: u32 = 3u;
+
+// The number of bins the histogram for `COLORING_HISTOGRAM` has; this has to match `HISTOGRAM_BINS` in `coloring.rs`.
+let HISTOGRAM_BINS: u32 = 4096u;
+
+// The result of iterating a single pixel.
+struct Pixel {
+    iters: u32;
+    flags: u32;
+    // The period of the cycle the pixel's orbit fell into, if it was found to be in the set that way, or 0 otherwise.
+    period: u32;
+    // |z|² after the last iteration, or of the attracting cycle if it's in the set, its period is known and `interior_coloring` isn't `INTERIOR_NONE`.
+    norm: f32;
+    // The gradient of the smooth iteration count in pixels, for lighting it like a height field, or 0 if it's in the set.
+    slope: vec2<f32>;
+    // The estimated distance from the pixel to the set, in pixels.
+    // If it's in the set, this is instead the distance to the edge of its hyperbolic component if the attracting cycle was found, or 0 if not.
+    distance: f32;
+    // The closest the pixel's orbit got to the orbit trap, if it's being tracked.
+    trap: f32;
+    // The pixel's stripe average, if it's being tracked and the pixel escaped.
+    stripe: f32;
+    // The argument of z after the last iteration, between -π and π, or 0 if it's in the set.
+    angle: f32;
+    // The attracting cycle's multiplier if it's in the set and the cycle was found, or 0 otherwise.
+    multiplier: vec2<f32>;
+    // The pixel's atom and Misiurewicz domains, if they're being tracked.
+    atom: u32;
+    misiurewicz: u32;
+};
+
+[[block]]
+struct Pixels {
+    pixels: [[stride(56)]] array<Pixel>;
+};
+
+[[group(0), binding(0)]] var<uniform> settings: Settings;
+
+fn complex_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x * b.x - a.y * b.y, a.x * b.y + a.y * b.x);
+}
+
+fn complex_div(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(a.x * b.x + a.y * b.y, a.y * b.x - a.x * b.y) / dot(b, b);
+}
+
+// Checks whether `c` is in the main cardioid or the period-2 bulb, which can be done without iterating it at all.
+// Returns the period of the component it's in, or 0 if it's in neither.
+//
+// `c` only has single precision, so points too close to the edge to be sure about are left to be iterated.
+// Domain colourings cover the inside of the set too, so they always need pixels to be iterated, and Julia sets don't have the same shape.
+fn known_period(c: vec2<f32>) -> u32 {
+    if (settings.julia != 0u || (settings.orbit_tracking & (TRACK_ATOM | TRACK_MISIUREWICZ)) != 0u) {
+        return 0u;
+    }
+
+    let margin = 0.000001;
+
+    let x = c.x - 0.25;
+    let q = x * x + c.y * c.y;
+    if (q * (q + x) < 0.25 * c.y * c.y - margin) {
+        return 1u;
+    }
+
+    let bulb_x = c.x + 1.0;
+    if (bulb_x * bulb_x + c.y * c.y < 0.0625 - margin) {
+        return 2u;
+    }
+
+    return 0u;
+}
+
+// How many plain iterations are done before Newton's method, and how many steps of it are used, to find attracting cycles.
+// These have to match `CYCLE_WARMUP` and `CYCLE_NEWTON_STEPS` in `interior.rs`.
+let CYCLE_WARMUP: u32 = 64u;
+let CYCLE_NEWTON_STEPS: u32 = 8u;
+
+// Fills in `pixel`'s attracting cycle for interior colouring, if it's in the set and its period is known.
+// `z` is somewhere along its orbit, and `size` is the pixel size for measuring the distance to the component's edge in pixels.
+// This has to match `Cycle::find` in `interior.rs`, although it's only single precision.
+fn add_cycle(pixel_in: Pixel, c: vec2<f32>, z_in: vec2<f32>, size: f32) -> Pixel {
+    var pixel = pixel_in;
+    if (settings.interior_coloring == INTERIOR_NONE || pixel.period == 0u || pixel.iters < settings.iterations) {
+        return pixel;
+    }
+
+    let one = vec2<f32>(1.0, 0.0);
+    var z = z_in;
+    for (var i = 0u; i < CYCLE_WARMUP; i = i + 1u) {
+        z = complex_mul(z, z) + c;
+    }
+
+    for (var step = 0u; step < CYCLE_NEWTON_STEPS; step = step + 1u) {
+        var w = z;
+        var dw = one;
+        for (var i = 0u; i < pixel.period; i = i + 1u) {
+            dw = 2.0 * complex_mul(w, dw);
+            w = complex_mul(w, w) + c;
+        }
+        // z' = z - (f^p(z) - z) / (d(f^p)/dz - 1)
+        z = z - complex_div(w - z, dw - one);
+    }
+
+    var w = z;
+    var dz = one;
+    var dc = vec2<f32>(0.0, 0.0);
+    var dzdz = vec2<f32>(0.0, 0.0);
+    var dcdz = vec2<f32>(0.0, 0.0);
+    for (var i = 0u; i < pixel.period; i = i + 1u) {
+        dcdz = 2.0 * (complex_mul(dc, dz) + complex_mul(w, dcdz));
+        dzdz = 2.0 * (complex_mul(dz, dz) + complex_mul(w, dzdz));
+        dc = 2.0 * complex_mul(w, dc) + one;
+        dz = 2.0 * complex_mul(w, dz);
+        w = complex_mul(w, w) + c;
+    }
+
+    let denominator = dcdz + complex_div(complex_mul(dzdz, dc), one - dz);
+    pixel.norm = dot(z, z);
+    pixel.multiplier = dz;
+    pixel.distance = (1.0 - dot(dz, dz)) / length(denominator) / size;
+    return pixel;
+}
+
+// The continuous iteration count of a pixel which escaped, which is between `iters` and `iters + 1` depending on how far past the bailout z got.
+// This has to match `smooth_iterations` in `coloring.rs`, the CPU version.
+fn smooth_iterations(pixel: Pixel) -> f32 {
+    // log|z| / log(bailout), which is between 1 and n since z can only get up to about bailoutⁿ in the last iteration.
+    let ratio = log(pixel.norm) / log(settings.bailout * settings.bailout);
+    return f32(pixel.iters) + 1.0 - log(max(ratio, 1.0)) / settings.log_degree;
+}
+
+// Estimates how far a point which escaped with |z|² = `norm` is from the set, given |dz/dc| measured in pixels.
+// This is the lower bound |z| ln|z| / 2|dz/dc|, which is within a factor of 4 of the real distance.
+// This has to match `distance_estimate` in `coloring.rs`.
+fn distance_estimate(norm: f32, derivative: f32) -> f32 {
+    // |z| ln|z| = sqrt(|z|²) ln(|z|²) / 2
+    return 0.25 * sqrt(norm) * log(norm) / derivative;
+}
+
+// Gets the gradient of the smooth iteration count of a point which escaped at `z`, given dz/dc measured in pixels as `derivative * 2^exponent`.
+// The smooth iteration count is n + 1 - logₖ(ln|z| / ln(bailout)) for a formula of degree k, so its gradient is -∇ln|z| / (ln k ln|z|),
+// and ∇ln|z| = (Re w, -Im w) where w = (dz/dc) / z.
+// This has to match `smooth_slope` in `coloring.rs`.
+fn smooth_slope(z: vec2<f32>, derivative: vec2<f32>, exponent: i32) -> vec2<f32> {
+    // Anything that overflows here is far too steep to light properly anyway, so it doesn't matter that the exponent gets clamped.
+    let w = complex_div(derivative, z) * exp2(f32(clamp(exponent, -126, 126)));
+    return -vec2<f32>(w.x, -w.y) / (settings.log_degree * 0.5 * log(dot(z, z)));
+}
+
+// The values built up along an orbit so far, for orbit trap, stripe average and domain colouring.
+// This has to match `OrbitStats` in `orbit.rs`.
+struct OrbitStats {
+    // The closest the orbit has gotten to the trap.
+    trap: f32;
+    // The sum of the stripe values of every point in the orbit, and the value of the last one.
+    stripe_sum: f32;
+    stripe_last: f32;
+    // The atom domain so far, and the |z|² it had there.
+    atom: u32;
+    atom_norm: f32;
+    // The Misiurewicz domain so far, and the |z_{q+1} - z_q|² it had there.
+    misiurewicz: u32;
+    misiurewicz_norm: f32;
+    // The previous point in the orbit.
+    previous: vec2<f32>;
+};
+
+fn orbit_stats_new() -> OrbitStats {
+    let far = 340282346638528859811704183484516925440.0;
+    return OrbitStats(far, 0.0, 0.0, 0u, far, 0u, far, vec2<f32>(0.0, 0.0));
+}
+
+// The distance from `z` to the orbit trap.
+fn trap_distance(z: vec2<f32>) -> f32 {
+    let offset = z - settings.trap_center;
+    // The offset rotated so that the trap's line lies along the real axis.
+    let along = offset.x * cos(settings.trap_angle) + offset.y * sin(settings.trap_angle);
+    let across = offset.y * cos(settings.trap_angle) - offset.x * sin(settings.trap_angle);
+
+    if (settings.trap_shape == TRAP_LINE) {
+        return abs(across);
+    } elseif (settings.trap_shape == TRAP_CROSS) {
+        return min(abs(across), abs(along));
+    }
+    return abs(length(offset) - settings.trap_radius);
+}
+
+// Adds `z`, the `n`th point of the orbit, only doing the work for whichever of the `TRACK_*` flags are in `settings.orbit_tracking`.
+fn orbit_stats_add(stats: OrbitStats, z: vec2<f32>, n: u32) -> OrbitStats {
+    var out = stats;
+    if ((settings.orbit_tracking & TRACK_TRAP) != 0u) {
+        out.trap = min(out.trap, trap_distance(z));
+    }
+    if ((settings.orbit_tracking & TRACK_STRIPES) != 0u) {
+        out.stripe_last = 0.5 * sin(settings.stripe_frequency * atan2(z.y, z.x)) + 0.5;
+        out.stripe_sum = out.stripe_sum + out.stripe_last;
+    }
+    if ((settings.orbit_tracking & TRACK_ATOM) != 0u) {
+        let norm = dot(z, z);
+        if (norm < out.atom_norm) {
+            out.atom = n;
+            out.atom_norm = norm;
+        }
+    }
+    if ((settings.orbit_tracking & TRACK_MISIUREWICZ) != 0u) {
+        // z₀ = 0 and z₁ = c, so the preperiod has to be at least 1.
+        let difference = z - out.previous;
+        let norm = dot(difference, difference);
+        if (n >= 2u && norm < out.misiurewicz_norm) {
+            out.misiurewicz = n - 1u;
+            out.misiurewicz_norm = norm;
+        }
+        out.previous = z;
+    }
+    return out;
+}
+
+// The stripe average of an orbit which escaped after `iters` iterations with |z|² = `norm`,
+// blended between the averages with and without the last point by the fractional part of the smooth iteration count.
+fn stripe_average(stats: OrbitStats, iters: u32, norm: f32) -> f32 {
+    let average = stats.stripe_sum / f32(iters);
+    var previous = average;
+    if (iters > 1u) {
+        previous = (stats.stripe_sum - stats.stripe_last) / f32(iters - 1u);
+    }
+
+    let ratio = log(norm) / log(settings.bailout * settings.bailout);
+    let fraction = 1.0 - log(max(ratio, 1.0)) / settings.log_degree;
+    return previous + fraction * (average - previous);
+}
+
+// Whether a pixel with `flags` gets left alone this pass, because it isn't on an edge and only edges are being supersampled.
+fn skip_sample(flags: u32) -> bool {
+    return settings.sample_pixels == SAMPLE_EDGES && (flags & EDGE) == 0u;
+}
+
+// The flags every pixel iterated this pass starts off with, which keeps the edges marked while they're supersampled.
+fn sample_flags() -> u32 {
+    if (settings.sample_pixels == SAMPLE_EDGES) {
+        return EDGE;
+    }
+    return 0u;
+}
+
+// Gets which bin of the histogram a pixel with a colouring value of `value` goes in.
+fn histogram_bin(value: f32) -> u32 {
+    return min(u32(max(value, 0.0) * f32(HISTOGRAM_BINS)), HISTOGRAM_BINS - 1u);
+}
+
+// Gets the offset in pixels of this pass's sample of the pixel at `id` from the center of the screen.
+fn pixel_offset(id: vec2<u32>) -> vec2<f32> {
+    let offset = vec2<f32>(id) + vec2<f32>(0.5, 0.5) + settings.sample_offset - settings.center;
+    // Flip around the y, since in pixel space y gets bigger going downwards, whereas on the complex plane it's the reverse.
+    return vec2<f32>(offset.x, -offset.y);
+}
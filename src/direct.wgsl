@@ -0,0 +1,198 @@
+// Iterates every pixel directly from z = 0 (or from the pixel, for a Julia set), without a reference orbit.
+// This is only accurate down to the precision of the arithmetic used, so it's only used for shallower zooms.
+//
+// z gets iterated with `formula`, which is generated by `Formula::wgsl` in `formula.rs` and prepended to this.
+
+[[group(0), binding(1)]] var<storage, read_write> pixels: Pixels;
+
+// Gets the pixel size as a regular f32, which is fine at the zooms these are used for.
+fn pixel_size() -> f32 {
+    return float_exp_to_f32(FloatExp(settings.pixel_size, settings.pixel_size_exponent));
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn single_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= settings.width || id.y >= settings.height) {
+        return;
+    }
+    if (skip_sample(pixels.pixels[id.y * settings.width + id.x].flags)) {
+        return;
+    }
+
+    let size = pixel_size();
+    let point = settings.camera + pixel_offset(id.xy) * size;
+
+    var c = point;
+    var z = vec2<f32>(0.0, 0.0);
+    // dz/dc measured in pixels, for estimating the distance to the set.
+    var derivative = vec2<f32>(0.0, 0.0);
+    // What gets added to the derivative each iteration.
+    var derivative_offset = vec2<f32>(size, 0.0);
+    if (settings.julia != 0u) {
+        // For a Julia set it's dz/dz₀ instead, which starts at 1 and has nothing added.
+        c = settings.julia_c;
+        z = point;
+        derivative = derivative_offset;
+        derivative_offset = vec2<f32>(0.0, 0.0);
+    }
+    var stats = orbit_stats_new();
+    var iters = 0u;
+    var period = 0u;
+    if (FORMULA_MANDELBROT) {
+        period = known_period(c);
+    }
+    if (period != 0u) {
+        iters = settings.iterations;
+    }
+
+    // Brent's algorithm: z is compared against a saved value, which gets replaced after twice as many iterations each time.
+    var saved = z;
+    var since_saved = 0u;
+    var check_len = 1u;
+    loop {
+        if (iters >= settings.iterations) {
+            break;
+        }
+
+        // (dz/dc)' = f'(z) dz/dc + 1, which needs to be scaled by the pixel size to be in pixels.
+        derivative = formula_derivative(z, derivative) + derivative_offset;
+        z = formula(z) + c;
+        iters = iters + 1u;
+        stats = orbit_stats_add(stats, z, iters);
+
+        if (dot(z, z) >= settings.bailout * settings.bailout) {
+            break;
+        }
+
+        since_saved = since_saved + 1u;
+        let difference = abs(z - saved);
+        if (max(difference.x, difference.y) < settings.period_tolerance) {
+            period = since_saved;
+            iters = settings.iterations;
+            break;
+        }
+
+        if (since_saved == check_len) {
+            saved = z;
+            since_saved = 0u;
+            check_len = check_len * 2u;
+        }
+    }
+
+    var slope = vec2<f32>(0.0, 0.0);
+    var distance = 0.0;
+    var stripe = 0.0;
+    var angle = 0.0;
+    if (iters < settings.iterations) {
+        slope = smooth_slope(z, derivative, 0);
+        distance = distance_estimate(dot(z, z), length(derivative));
+        stripe = stripe_average(stats, iters, dot(z, z));
+        angle = atan2(z.y, z.x);
+    }
+
+    var pixel = Pixel(iters, sample_flags(), period, dot(z, z), slope, distance, stats.trap, stripe, angle, vec2<f32>(0.0, 0.0), stats.atom, stats.misiurewicz);
+    // Finding the attracting cycle relies on the formula being z² + c.
+    if (FORMULA_MANDELBROT) {
+        pixel = add_cycle(pixel, c, z, size);
+    }
+    pixels.pixels[id.y * settings.width + id.x] = pixel;
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn double_single_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= settings.width || id.y >= settings.height) {
+        return;
+    }
+    if (skip_sample(pixels.pixels[id.y * settings.width + id.x].flags)) {
+        return;
+    }
+
+    // The pixel size only has single precision, but the product doesn't lose any of it.
+    let offset = pixel_offset(id.xy);
+    let size = pixel_size();
+    let point_real = ds_add(vec2<f32>(settings.camera.x, settings.camera_low.x), two_product(offset.x, size));
+    let point_imag = ds_add(vec2<f32>(settings.camera.y, settings.camera_low.y), two_product(offset.y, size));
+
+    var c_real = point_real;
+    var c_imag = point_imag;
+    var z_real = vec2<f32>(0.0, 0.0);
+    var z_imag = vec2<f32>(0.0, 0.0);
+    // The derivative doesn't need to be anywhere near as precise as z, so it's only single precision.
+    var derivative = vec2<f32>(0.0, 0.0);
+    var derivative_offset = vec2<f32>(size, 0.0);
+    if (settings.julia != 0u) {
+        c_real = vec2<f32>(settings.julia_c.x, settings.julia_c_low.x);
+        c_imag = vec2<f32>(settings.julia_c.y, settings.julia_c_low.y);
+        z_real = point_real;
+        z_imag = point_imag;
+        derivative = derivative_offset;
+        derivative_offset = vec2<f32>(0.0, 0.0);
+    }
+    var stats = orbit_stats_new();
+    var iters = 0u;
+    var period = 0u;
+    if (FORMULA_MANDELBROT) {
+        period = known_period(vec2<f32>(c_real.x, c_imag.x));
+    }
+    if (period != 0u) {
+        iters = settings.iterations;
+    }
+
+    // Brent's algorithm, like in `single_main`.
+    var saved_real = z_real;
+    var saved_imag = z_imag;
+    var since_saved = 0u;
+    var check_len = 1u;
+    loop {
+        if (iters >= settings.iterations) {
+            break;
+        }
+
+        derivative = formula_derivative(vec2<f32>(z_real.x, z_imag.x), derivative) + derivative_offset;
+
+        let w = formula_double_single(DsComplex(z_real, z_imag));
+        z_real = ds_add(w.real, c_real);
+        z_imag = ds_add(w.imag, c_imag);
+        iters = iters + 1u;
+        stats = orbit_stats_add(stats, vec2<f32>(z_real.x, z_imag.x), iters);
+
+        // The low halves can't make a difference to whether it's escaped.
+        if (z_real.x * z_real.x + z_imag.x * z_imag.x >= settings.bailout * settings.bailout) {
+            break;
+        }
+
+        // The high half of the difference is all that's needed to compare it.
+        since_saved = since_saved + 1u;
+        let difference = abs(vec2<f32>(ds_sub(z_real, saved_real).x, ds_sub(z_imag, saved_imag).x));
+        if (max(difference.x, difference.y) < settings.period_tolerance) {
+            period = since_saved;
+            iters = settings.iterations;
+            break;
+        }
+
+        if (since_saved == check_len) {
+            saved_real = z_real;
+            saved_imag = z_imag;
+            since_saved = 0u;
+            check_len = check_len * 2u;
+        }
+    }
+
+    let norm = z_real.x * z_real.x + z_imag.x * z_imag.x;
+    var slope = vec2<f32>(0.0, 0.0);
+    var distance = 0.0;
+    var stripe = 0.0;
+    var angle = 0.0;
+    if (iters < settings.iterations) {
+        slope = smooth_slope(vec2<f32>(z_real.x, z_imag.x), derivative, 0);
+        distance = distance_estimate(norm, length(derivative));
+        stripe = stripe_average(stats, iters, norm);
+        angle = atan2(z_imag.x, z_real.x);
+    }
+
+    var pixel = Pixel(iters, sample_flags(), period, norm, slope, distance, stats.trap, stripe, angle, vec2<f32>(0.0, 0.0), stats.atom, stats.misiurewicz);
+    if (FORMULA_MANDELBROT) {
+        pixel = add_cycle(pixel, vec2<f32>(c_real.x, c_imag.x), vec2<f32>(z_real.x, z_imag.x), size);
+    }
+    pixels.pixels[id.y * settings.width + id.x] = pixel;
+}
@@ -0,0 +1,69 @@
+// Double-single arithmetic: numbers are represented as the unevaluated sum of two f32s, `hi + lo`, stored as a `vec2<f32>`,
+// which gives about 48 bits of precision rather than 24. See `DoubleSingle` in `num.rs` for the CPU version.
+//
+// All of this relies on every operation being rounded exactly as IEEE 754 says, so it'll fall apart if the driver reorders or fuses anything.
+
+// Adds two f32s, returning the rounded sum and the rounding error.
+fn two_sum(a: f32, b: f32) -> vec2<f32> {
+    let sum = a + b;
+    let b_virtual = sum - a;
+    let a_virtual = sum - b_virtual;
+    return vec2<f32>(sum, (a - a_virtual) + (b - b_virtual));
+}
+
+// Like `two_sum`, but only correct if |a| >= |b|.
+fn quick_two_sum(a: f32, b: f32) -> vec2<f32> {
+    let sum = a + b;
+    return vec2<f32>(sum, b - (sum - a));
+}
+
+// Splits an f32 into two halves with 12 bits of mantissa each, so that multiplying them together is exact.
+fn split(a: f32) -> vec2<f32> {
+    // 2^12 + 1
+    let t = 4097.0 * a;
+    let hi = t - (t - a);
+    return vec2<f32>(hi, a - hi);
+}
+
+// Multiplies two f32s, returning the rounded product and the rounding error.
+fn two_product(a: f32, b: f32) -> vec2<f32> {
+    let product = a * b;
+    let a_split = split(a);
+    let b_split = split(b);
+    let error = ((a_split.x * b_split.x - product) + a_split.x * b_split.y + a_split.y * b_split.x) + a_split.y * b_split.y;
+    return vec2<f32>(product, error);
+}
+
+fn ds_add(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let high = two_sum(a.x, b.x);
+    let low = two_sum(a.y, b.y);
+    let sum = quick_two_sum(high.x, high.y + low.x);
+    return quick_two_sum(sum.x, sum.y + low.y);
+}
+
+fn ds_sub(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    return ds_add(a, -b);
+}
+
+fn ds_mul(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let product = two_product(a.x, b.x);
+    return quick_two_sum(product.x, product.y + (a.x * b.y + a.y * b.x));
+}
+
+fn ds_abs(a: vec2<f32>) -> vec2<f32> {
+    // The high part has the number's sign, since |lo| is at most half an ulp of hi.
+    return select(a, -a, a.x < 0.0);
+}
+
+// A complex number with double-single real and imaginary parts.
+struct DsComplex {
+    real: vec2<f32>;
+    imag: vec2<f32>;
+};
+
+fn ds_complex_mul(a: DsComplex, b: DsComplex) -> DsComplex {
+    return DsComplex(
+        ds_sub(ds_mul(a.real, b.real), ds_mul(a.imag, b.imag)),
+        ds_add(ds_mul(a.real, b.imag), ds_mul(a.imag, b.real)),
+    );
+}
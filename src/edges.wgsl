@@ -0,0 +1,39 @@
+// Finds the pixels which adaptive supersampling supersamples: the ones whose iteration counts are far enough from their
+// neighbours', or which are on the other side of the edge of the set from one of them.
+
+[[group(0), binding(1)]] var<storage, read_write> pixels: Pixels;
+
+// Whether a pixel with `iters` iterations and the one at `other` are far enough apart to make it an edge.
+fn differs(iters: u32, other: vec2<i32>) -> bool {
+    if (other.x < 0 || other.y < 0 || other.x >= i32(settings.width) || other.y >= i32(settings.height)) {
+        return false;
+    }
+
+    let other_iters = pixels.pixels[u32(other.y) * settings.width + u32(other.x)].iters;
+    if ((iters >= settings.iterations) != (other_iters >= settings.iterations)) {
+        return true;
+    }
+    return max(iters, other_iters) - min(iters, other_iters) >= settings.edge_threshold;
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= settings.width || id.y >= settings.height) {
+        return;
+    }
+
+    let index = id.y * settings.width + id.x;
+    let iters = pixels.pixels[index].iters;
+    let position = vec2<i32>(id.xy);
+    let edge = differs(iters, position + vec2<i32>(1, 0))
+        || differs(iters, position + vec2<i32>(-1, 0))
+        || differs(iters, position + vec2<i32>(0, 1))
+        || differs(iters, position + vec2<i32>(0, -1));
+
+    // Only the flags get written, so this doesn't race with the neighbours reading this pixel's iteration count.
+    var flags = pixels.pixels[index].flags & ~EDGE;
+    if (edge) {
+        flags = flags | EDGE;
+    }
+    pixels.pixels[index].flags = flags;
+}
@@ -0,0 +1,278 @@
+// Iterates every pixel directly using fixed-point numbers, which are exact but much slower than floats.
+// `{limbs}` gets replaced with the number of limbs each number needs before this is compiled.
+
+// The number of 32-bit limbs in each number, including the integer limb.
+let limbs: u32 = {limbs}u;
+
+// A signed fixed-point number, stored as a big-endian two's complement integer whose first limb is the integer portion.
+// This has the same value as a `Component` with the same limbs, since a two's complement number's sign only affects the top limb.
+type Fixed = array<u32, limbs>;
+
+[[block]]
+struct FixedPointSettings {
+    // The real and imaginary parts of the camera's position, followed by the pixel size and then the real and imaginary parts of the Julia set's c.
+    limbs: [[stride(4)]] array<u32>;
+};
+
+[[group(0), binding(1)]] var<storage, read_write> pixels: Pixels;
+[[group(0), binding(4)]] var<storage, read> fixed_point: FixedPointSettings;
+
+fn fixed_zero() -> Fixed {
+    var out: Fixed;
+    for (var i = 0u; i < limbs; i = i + 1u) {
+        out[i] = 0u;
+    }
+    return out;
+}
+
+// Reads the number starting at `offset` limbs into `fixed_point`.
+fn fixed_load(offset: u32) -> Fixed {
+    var out: Fixed;
+    for (var i = 0u; i < limbs; i = i + 1u) {
+        out[i] = fixed_point.limbs[offset + i];
+    }
+    return out;
+}
+
+fn fixed_is_negative(a: Fixed) -> bool {
+    return a[0] >= 2147483648u;
+}
+
+fn fixed_add(a_in: Fixed, b_in: Fixed) -> Fixed {
+    var a = a_in;
+    var b = b_in;
+    var out: Fixed;
+    var carry = 0u;
+    for (var i = i32(limbs) - 1; i >= 0; i = i - 1) {
+        let sum = a[i] + b[i];
+        let total = sum + carry;
+        carry = select(0u, 1u, sum < a[i] || total < sum);
+        out[i] = total;
+    }
+    return out;
+}
+
+fn fixed_neg(a_in: Fixed) -> Fixed {
+    var a = a_in;
+    var out: Fixed;
+    // Flip all the bits and add 1.
+    var carry = 1u;
+    for (var i = i32(limbs) - 1; i >= 0; i = i - 1) {
+        let total = ~a[i] + carry;
+        carry = select(0u, 1u, total < carry);
+        out[i] = total;
+    }
+    return out;
+}
+
+fn fixed_sub(a: Fixed, b: Fixed) -> Fixed {
+    return fixed_add(a, fixed_neg(b));
+}
+
+fn fixed_abs(a: Fixed) -> Fixed {
+    if (fixed_is_negative(a)) {
+        return fixed_neg(a);
+    }
+    return a;
+}
+
+// Converts to an f32, which only needs to look at the first few limbs.
+fn fixed_to_f32(a: Fixed) -> f32 {
+    var magnitude = fixed_abs(a);
+    var out = 0.0;
+    var scale = 1.0;
+    for (var i = 0u; i < min(limbs, 4u); i = i + 1u) {
+        out = out + f32(magnitude[i]) * scale;
+        scale = scale / 4294967296.0;
+    }
+
+    if (fixed_is_negative(a)) {
+        return -out;
+    }
+    return out;
+}
+
+// Multiplies two 32-bit integers, giving the high and low halves of the 64-bit result.
+fn mul_wide(a: u32, b: u32) -> vec2<u32> {
+    // Split them into 16-bit halves, whose products can't overflow.
+    let a_low = a & 65535u;
+    let a_high = a >> 16u;
+    let b_low = b & 65535u;
+    let b_high = b >> 16u;
+
+    let low_low = a_low * b_low;
+    let high_low = a_high * b_low;
+    let low_high = a_low * b_high;
+    let high_high = a_high * b_high;
+
+    // The sum of the middle 16-bit segments, which can spill over into the high half.
+    let middle = (low_low >> 16u) + (high_low & 65535u) + (low_high & 65535u);
+
+    let low = (middle << 16u) | (low_low & 65535u);
+    let high = high_high + (high_low >> 16u) + (low_high >> 16u) + (middle >> 16u);
+    return vec2<u32>(high, low);
+}
+
+fn fixed_mul(a_in: Fixed, b_in: Fixed) -> Fixed {
+    let negative = fixed_is_negative(a_in) != fixed_is_negative(b_in);
+    var a = fixed_abs(a_in);
+    var b = fixed_abs(b_in);
+
+    // Primary-school style multiplication of the magnitudes, one limb at a time.
+    // The product of limbs `i` and `j` lands in limb `i + j` (and the one above it for the high half),
+    // so anything which would land past the last limb is left out, which truncates the result.
+    var out = fixed_zero();
+    for (var i = 0u; i < limbs; i = i + 1u) {
+        for (var j = 0u; i + j <= limbs && j < limbs; j = j + 1u) {
+            let product = mul_wide(a[i], b[j]);
+
+            // Add the low half to limb `i + j` and the high half to the limb above it, carrying as far up as necessary.
+            // The high half of the integer limbs' product would be above the integer limb, which can't happen for numbers this small.
+            for (var half = 0u; half < 2u; half = half + 1u) {
+                var index = i32(i + j) - i32(half);
+                var carry = select(product.y, product.x, half == 1u);
+                if (index >= i32(limbs)) {
+                    carry = 0u;
+                }
+                loop {
+                    if (carry == 0u || index < 0) {
+                        break;
+                    }
+
+                    let sum = out[index] + carry;
+                    carry = select(0u, 1u, sum < carry);
+                    out[index] = sum;
+                    index = index - 1;
+                }
+            }
+        }
+    }
+
+    if (negative) {
+        return fixed_neg(out);
+    }
+    return out;
+}
+
+// Converts a pixel offset to a fixed-point number.
+// This only keeps the first 32 bits after the binary point, which is plenty for offsets since they're always a whole number of half-pixels.
+fn fixed_from_offset(x: f32) -> Fixed {
+    var out = fixed_zero();
+    let magnitude = abs(x);
+    let int = floor(magnitude);
+    out[0] = u32(int);
+    out[1] = u32((magnitude - int) * 4294967296.0);
+    if (x < 0.0) {
+        return fixed_neg(out);
+    }
+    return out;
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= settings.width || id.y >= settings.height) {
+        return;
+    }
+    if (skip_sample(pixels.pixels[id.y * settings.width + id.x].flags)) {
+        return;
+    }
+
+    let offset = pixel_offset(id.xy);
+    let pixel_size = fixed_load(2u * limbs);
+    let point_real = fixed_add(fixed_load(0u), fixed_mul(fixed_from_offset(offset.x), pixel_size));
+    let point_imag = fixed_add(fixed_load(limbs), fixed_mul(fixed_from_offset(offset.y), pixel_size));
+
+    var c_real = point_real;
+    var c_imag = point_imag;
+    var z_real = fixed_zero();
+    var z_imag = fixed_zero();
+    // The squares of the real and imaginary parts, which get reused between the escape check and the next iteration.
+    var real2 = fixed_zero();
+    var imag2 = fixed_zero();
+    var norm = 0.0;
+    // z and dz/dc measured in pixels, both only in single precision, for estimating the distance to the set.
+    var z = vec2<f32>(0.0, 0.0);
+    var derivative = vec2<f32>(0.0, 0.0);
+    let size = fixed_to_f32(pixel_size);
+    var derivative_offset = vec2<f32>(size, 0.0);
+    if (settings.julia != 0u) {
+        // For a Julia set the pixel is z₀ and the derivative is dz/dz₀ instead, like in `direct.wgsl`.
+        c_real = fixed_load(3u * limbs);
+        c_imag = fixed_load(4u * limbs);
+        z_real = point_real;
+        z_imag = point_imag;
+        real2 = fixed_mul(z_real, z_real);
+        imag2 = fixed_mul(z_imag, z_imag);
+        z = vec2<f32>(fixed_to_f32(z_real), fixed_to_f32(z_imag));
+        derivative = derivative_offset;
+        derivative_offset = vec2<f32>(0.0, 0.0);
+    }
+    var stats = orbit_stats_new();
+    var iters = 0u;
+    var period = known_period(vec2<f32>(fixed_to_f32(c_real), fixed_to_f32(c_imag)));
+    if (period != 0u) {
+        iters = settings.iterations;
+    }
+
+    // Brent's algorithm, like in `direct.wgsl`.
+    var saved_real = z_real;
+    var saved_imag = z_imag;
+    var since_saved = 0u;
+    var check_len = 1u;
+    loop {
+        if (iters >= settings.iterations) {
+            break;
+        }
+
+        derivative = 2.0 * complex_mul(z, derivative) + derivative_offset;
+
+        // (a + bi)^2 = a^2 - b^2 + 2abi
+        let imag = fixed_mul(z_real, z_imag);
+        z_imag = fixed_add(fixed_add(imag, imag), c_imag);
+        z_real = fixed_add(fixed_sub(real2, imag2), c_real);
+        iters = iters + 1u;
+
+        // Check for escaping before squaring anything, since anything big enough to escape could overflow the integer limb.
+        // The bailout is small enough that the squares of anything below it can't.
+        let real = fixed_to_f32(z_real);
+        let imag = fixed_to_f32(z_imag);
+        z = vec2<f32>(real, imag);
+        stats = orbit_stats_add(stats, z, iters);
+        norm = dot(z, z);
+        if (norm >= settings.bailout * settings.bailout) {
+            break;
+        }
+
+        real2 = fixed_mul(z_real, z_real);
+        imag2 = fixed_mul(z_imag, z_imag);
+
+        since_saved = since_saved + 1u;
+        let difference = abs(vec2<f32>(fixed_to_f32(fixed_sub(z_real, saved_real)), fixed_to_f32(fixed_sub(z_imag, saved_imag))));
+        if (max(difference.x, difference.y) < settings.period_tolerance) {
+            period = since_saved;
+            iters = settings.iterations;
+            break;
+        }
+
+        if (since_saved == check_len) {
+            saved_real = z_real;
+            saved_imag = z_imag;
+            since_saved = 0u;
+            check_len = check_len * 2u;
+        }
+    }
+
+    var slope = vec2<f32>(0.0, 0.0);
+    var distance = 0.0;
+    var stripe = 0.0;
+    var angle = 0.0;
+    if (iters < settings.iterations) {
+        slope = smooth_slope(z, derivative, 0);
+        distance = distance_estimate(norm, length(derivative));
+        stripe = stripe_average(stats, iters, norm);
+        angle = atan2(z.y, z.x);
+    }
+
+    let pixel = Pixel(iters, sample_flags(), period, norm, slope, distance, stats.trap, stripe, angle, vec2<f32>(0.0, 0.0), stats.atom, stats.misiurewicz);
+    pixels.pixels[id.y * settings.width + id.x] = add_cycle(pixel, vec2<f32>(fixed_to_f32(c_real), fixed_to_f32(c_imag)), z, size);
+}
@@ -0,0 +1,114 @@
+// Floating point numbers with an extended exponent range (see `FloatExp` in `num.rs`),
+// for values too small to fit in an f32, like pixel sizes and deltas at deep zooms.
+
+// `mantissa * 2^exponent`, where the mantissa is either 0 or between 0.5 and 1 in magnitude.
+struct FloatExp {
+    mantissa: f32;
+    exponent: i32;
+};
+
+// A complex number whose parts share an exponent: `mantissa * 2^exponent`.
+// The larger of the mantissa's parts is either 0 or between 0.5 and 1 in magnitude.
+struct ComplexExp {
+    mantissa: vec2<f32>;
+    exponent: i32;
+};
+
+// The exponent used for 0, so that it compares smaller than any other number.
+let ZERO_EXPONENT: i32 = -1000000000;
+
+fn float_exp_normalize(num: FloatExp) -> FloatExp {
+    if (num.mantissa == 0.0) {
+        return FloatExp(0.0, ZERO_EXPONENT);
+    }
+
+    let shift = i32(floor(log2(abs(num.mantissa)))) + 1;
+    return FloatExp(num.mantissa * exp2(f32(-shift)), num.exponent + shift);
+}
+
+fn float_exp_mul(a: FloatExp, b: FloatExp) -> FloatExp {
+    return float_exp_normalize(FloatExp(a.mantissa * b.mantissa, a.exponent + b.exponent));
+}
+
+fn float_exp_add(a: FloatExp, b: FloatExp) -> FloatExp {
+    if (a.mantissa == 0.0) {
+        return b;
+    } elseif (b.mantissa == 0.0) {
+        return a;
+    }
+
+    // Line up the smaller number with the larger one's exponent; if it's too small to matter, it just underflows to 0.
+    if (a.exponent >= b.exponent) {
+        return float_exp_normalize(FloatExp(a.mantissa + b.mantissa * exp2(f32(b.exponent - a.exponent)), a.exponent));
+    } else {
+        return float_exp_normalize(FloatExp(b.mantissa + a.mantissa * exp2(f32(a.exponent - b.exponent)), b.exponent));
+    }
+}
+
+// Whether `a` is less than `b`, assuming that neither is negative.
+fn float_exp_less(a: FloatExp, b: FloatExp) -> bool {
+    if (b.mantissa == 0.0) {
+        return false;
+    } elseif (a.mantissa == 0.0) {
+        return true;
+    } elseif (a.exponent != b.exponent) {
+        return a.exponent < b.exponent;
+    }
+    return a.mantissa < b.mantissa;
+}
+
+fn float_exp_to_f32(num: FloatExp) -> f32 {
+    return num.mantissa * exp2(f32(num.exponent));
+}
+
+fn complex_exp_normalize(num: ComplexExp) -> ComplexExp {
+    let largest = max(abs(num.mantissa.x), abs(num.mantissa.y));
+    if (largest == 0.0) {
+        return ComplexExp(vec2<f32>(0.0, 0.0), ZERO_EXPONENT);
+    }
+
+    let shift = i32(floor(log2(largest))) + 1;
+    return ComplexExp(num.mantissa * exp2(f32(-shift)), num.exponent + shift);
+}
+
+fn complex_exp_add(a: ComplexExp, b: ComplexExp) -> ComplexExp {
+    if (a.mantissa.x == 0.0 && a.mantissa.y == 0.0) {
+        return b;
+    } elseif (b.mantissa.x == 0.0 && b.mantissa.y == 0.0) {
+        return a;
+    }
+
+    if (a.exponent >= b.exponent) {
+        return complex_exp_normalize(ComplexExp(a.mantissa + b.mantissa * exp2(f32(b.exponent - a.exponent)), a.exponent));
+    } else {
+        return complex_exp_normalize(ComplexExp(b.mantissa + a.mantissa * exp2(f32(a.exponent - b.exponent)), b.exponent));
+    }
+}
+
+fn complex_exp_mul(a: ComplexExp, b: ComplexExp) -> ComplexExp {
+    let mantissa = vec2<f32>(
+        a.mantissa.x * b.mantissa.x - a.mantissa.y * b.mantissa.y,
+        a.mantissa.x * b.mantissa.y + a.mantissa.y * b.mantissa.x,
+    );
+    return complex_exp_normalize(ComplexExp(mantissa, a.exponent + b.exponent));
+}
+
+// Multiplies an extended complex number by a regular one.
+fn complex_exp_mul_complex(a: ComplexExp, b: vec2<f32>) -> ComplexExp {
+    let mantissa = vec2<f32>(a.mantissa.x * b.x - a.mantissa.y * b.y, a.mantissa.x * b.y + a.mantissa.y * b.x);
+    return complex_exp_normalize(ComplexExp(mantissa, a.exponent));
+}
+
+// Multiplies a regular complex number by an extended real number.
+fn complex_mul_float_exp(a: vec2<f32>, b: FloatExp) -> ComplexExp {
+    return complex_exp_normalize(ComplexExp(a * b.mantissa, b.exponent));
+}
+
+fn complex_exp_norm_sqr(num: ComplexExp) -> FloatExp {
+    return float_exp_normalize(FloatExp(dot(num.mantissa, num.mantissa), 2 * num.exponent));
+}
+
+// Converts back to a regular complex number, which will underflow to 0 if it's too small.
+fn complex_exp_to_complex(num: ComplexExp) -> vec2<f32> {
+    return num.mantissa * exp2(f32(num.exponent));
+}
@@ -0,0 +1,479 @@
+//! The escape-time formulas pixels can be iterated with, all of the form z ↦ f(z) + c.
+//!
+//! Besides the Mandelbrot set's z² + c, there are the Multibrot sets zⁿ + c, and a few variations on z² + c which fold z
+//! or z² with absolute values or conjugates first:
+//!
+//! ```text
+//! Burning Ship  (|Re z| + i|Im z|)² + c
+//! Tricorn       z̄² + c
+//! Celtic        |Re z²| + i Im z² + c
+//! Buffalo       |Re z²| + i|Im z²| + c
+//! ```
+//!
+//! The iteration shaders get each formula's code generated by `Formula::wgsl` and spliced in before they're compiled,
+//! so there's a pipeline for each formula that's been used. Fixed point and perturbation are written specifically for z² + c,
+//! so the other formulas can only be zoomed in as far as double-single precision can go (see `State::max_zoom`).
+//!
+//! The folded formulas aren't holomorphic, so their derivatives are only taken along the real axis,
+//! which makes distance estimation and lighting approximate for them. Interior colouring assumes z² + c too.
+
+use crate::coloring;
+use crate::Pixel;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Formula {
+    /// zⁿ + c; n = 2 is the Mandelbrot set.
+    Multibrot(Power),
+    BurningShip,
+    /// Also known as the Mandelbar set.
+    Tricorn,
+    Celtic,
+    Buffalo,
+}
+
+impl Default for Formula {
+    fn default() -> Self {
+        Self::MANDELBROT
+    }
+}
+
+/// The power z gets raised to in a Multibrot set, which is at least 2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Power(u32);
+
+impl Power {
+    /// Returns `None` if `n` is less than 2, since z⁰ + c and z¹ + c don't have anything to see.
+    pub fn new(n: u32) -> Option<Self> {
+        if n >= 2 {
+            Some(Self(n))
+        } else {
+            None
+        }
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// The power one higher than this.
+    pub fn next(self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+/// How z gets folded before it's squared, for the variations on z² + c.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fold {
+    None,
+    /// |Re z| + i|Im z|.
+    Abs,
+    /// z̄.
+    Conjugate,
+}
+
+impl Formula {
+    /// z² + c.
+    pub const MANDELBROT: Self = Self::Multibrot(Power(2));
+
+    /// zⁿ + c, or `None` if `n` is less than 2.
+    pub fn multibrot(n: u32) -> Option<Self> {
+        Power::new(n).map(Self::Multibrot)
+    }
+
+    /// Whether this is z² + c, which is the only formula fixed point, perturbation and the interior colourings support.
+    pub fn is_mandelbrot(self) -> bool {
+        self == Self::MANDELBROT
+    }
+
+    /// The power z gets raised to, if it's a Multibrot set with a higher power than 2.
+    fn power(self) -> Option<u32> {
+        match self {
+            Self::Multibrot(Power(n)) if n > 2 => Some(n),
+            _ => None,
+        }
+    }
+
+    /// ln of the degree of f, which is how much faster than exponentially |z| grows once it's big.
+    ///
+    /// Smooth colouring takes its logarithm to this base, so that it comes out continuous for every formula.
+    pub fn log_degree(self) -> f32 {
+        (self.power().unwrap_or(2) as f32).ln()
+    }
+
+    /// How z gets folded before squaring it, and whether the real and imaginary parts of the square get their absolute values taken,
+    /// for everything but the higher powers.
+    fn folds(self) -> (Fold, [bool; 2]) {
+        match self {
+            Self::Multibrot(_) => (Fold::None, [false, false]),
+            Self::BurningShip => (Fold::Abs, [false, false]),
+            Self::Tricorn => (Fold::Conjugate, [false, false]),
+            Self::Celtic => (Fold::None, [true, false]),
+            Self::Buffalo => (Fold::None, [true, true]),
+        }
+    }
+
+    /// f(z), the formula without c added.
+    ///
+    /// This has to match `formula` in the code generated by `wgsl`.
+    pub fn apply(self, z: [f64; 2]) -> [f64; 2] {
+        if let Some(n) = self.power() {
+            let mut w = z;
+            for _ in 1..n {
+                w = mul(w, z);
+            }
+            return w;
+        }
+
+        let (fold, abs) = self.folds();
+        let square = mul(fold_z(fold, z), fold_z(fold, z));
+        fold_square(abs, square, square)
+    }
+
+    /// The derivative of f at `z` applied to `dz`, which is f'(z) dz for the holomorphic formulas,
+    /// or the derivative along `dz`'s direction for the folded ones.
+    ///
+    /// This has to match `formula_derivative` in the code generated by `wgsl`.
+    pub fn derivative(self, z: [f64; 2], dz: [f64; 2]) -> [f64; 2] {
+        if let Some(n) = self.power() {
+            // n zⁿ⁻¹ dz
+            let mut w = [dz[0] * n as f64, dz[1] * n as f64];
+            for _ in 1..n {
+                w = mul(w, z);
+            }
+            return w;
+        }
+
+        let (fold, abs) = self.folds();
+        let folded = fold_z(fold, z);
+        let folded_dz = match fold {
+            Fold::None => dz,
+            Fold::Abs => [z[0].signum() * dz[0], z[1].signum() * dz[1]],
+            Fold::Conjugate => [dz[0], -dz[1]],
+        };
+        let square = mul(folded, folded);
+        let derivative = mul(folded, folded_dz);
+        fold_square(abs, square, [2.0 * derivative[0], 2.0 * derivative[1]])
+    }
+
+    /// Iterates a pixel on the CPU, for checking the GPU's results against: `point` is c, or z₀ if `julia` is the c of a Julia set.
+    /// The derivative is measured in units of `pixel_size`, like the GPU's.
+    ///
+    /// This is a CPU version of `single_main` in `direct.wgsl` (without periodicity checking or orbit tracking), in `f64`s.
+    pub fn iterate(
+        self,
+        point: [f64; 2],
+        julia: Option<[f64; 2]>,
+        iterations: u32,
+        bailout: f32,
+        pixel_size: f64,
+    ) -> Pixel {
+        let (mut z, c, mut derivative, derivative_offset) = match julia {
+            Some(c) => (point, c, [pixel_size, 0.0], [0.0, 0.0]),
+            None => ([0.0, 0.0], point, [0.0, 0.0], [pixel_size, 0.0]),
+        };
+
+        let mut iters = 0;
+        while iters < iterations {
+            let step = self.derivative(z, derivative);
+            derivative = [
+                step[0] + derivative_offset[0],
+                step[1] + derivative_offset[1],
+            ];
+            let w = self.apply(z);
+            z = [w[0] + c[0], w[1] + c[1]];
+            iters += 1;
+
+            if z[0] * z[0] + z[1] * z[1] >= bailout as f64 * bailout as f64 {
+                break;
+            }
+        }
+
+        let norm = (z[0] * z[0] + z[1] * z[1]) as f32;
+        let (slope, distance, angle) = if iters < iterations {
+            let z = [z[0] as f32, z[1] as f32];
+            let derivative = [derivative[0] as f32, derivative[1] as f32];
+            (
+                coloring::smooth_slope(z, derivative, self.log_degree()),
+                coloring::distance_estimate(norm, derivative[0].hypot(derivative[1])),
+                z[1].atan2(z[0]),
+            )
+        } else {
+            ([0.0; 2], 0.0, 0.0)
+        };
+
+        Pixel {
+            iters,
+            norm,
+            slope,
+            distance,
+            angle,
+            ..Pixel::default()
+        }
+    }
+
+    /// Generates the WGSL for this formula, which the iteration shaders need prepended (after `doublesingle.wgsl`):
+    ///
+    /// - `FORMULA_MANDELBROT`, whether it's z² + c, which the known components and interior colourings need.
+    /// - `formula(z)` and `formula_derivative(z, dz)`, like `apply` and `derivative`.
+    /// - `formula_double_single(z)`, `formula` in double-single arithmetic.
+    pub fn wgsl(self) -> String {
+        let mut out = format!(
+            "// The formula {:?}, generated by `Formula::wgsl` in `formula.rs`.\n\nlet FORMULA_MANDELBROT: bool = {};\n\n",
+            self,
+            self.is_mandelbrot(),
+        );
+
+        if let Some(n) = self.power() {
+            out += &format!(
+                "\
+fn formula(z: vec2<f32>) -> vec2<f32> {{
+    var w = z;
+    for (var i = 1u; i < {n}u; i = i + 1u) {{
+        w = complex_mul(w, z);
+    }}
+    return w;
+}}
+
+fn formula_derivative(z: vec2<f32>, dz: vec2<f32>) -> vec2<f32> {{
+    var w = dz * {n}.0;
+    for (var i = 1u; i < {n}u; i = i + 1u) {{
+        w = complex_mul(w, z);
+    }}
+    return w;
+}}
+
+fn formula_double_single(z: DsComplex) -> DsComplex {{
+    var w = z;
+    for (var i = 1u; i < {n}u; i = i + 1u) {{
+        w = ds_complex_mul(w, z);
+    }}
+    return w;
+}}
+",
+                n = n,
+            );
+            return out;
+        }
+
+        let (fold, abs) = self.folds();
+        let (fold_z, fold_dz) = match fold {
+            Fold::None => ("z_in", "dz_in"),
+            Fold::Abs => ("abs(z_in)", "sign(z_in) * dz_in"),
+            Fold::Conjugate => ("vec2<f32>(z_in.x, -z_in.y)", "vec2<f32>(dz_in.x, -dz_in.y)"),
+        };
+        let fold_real = match fold {
+            Fold::None => "",
+            Fold::Abs => "    real = ds_abs(real);\n    imag = ds_abs(imag);\n",
+            Fold::Conjugate => "    imag = -imag;\n",
+        };
+        let part = |i: usize, value: &str, sign: &str| {
+            let component = ["x", "y"][i];
+            if abs[i] {
+                format!("{}{}.{}", sign, value, component)
+            } else {
+                format!("{}.{}", value, component)
+            }
+        };
+        let ds_part = |i: usize, value: &str| {
+            if abs[i] {
+                format!("ds_abs({})", value)
+            } else {
+                value.to_string()
+            }
+        };
+
+        out += &format!(
+            "\
+fn formula(z_in: vec2<f32>) -> vec2<f32> {{
+    let z = {fold_z};
+    let square = complex_mul(z, z);
+    return vec2<f32>({square_real}, {square_imag});
+}}
+
+fn formula_derivative(z_in: vec2<f32>, dz_in: vec2<f32>) -> vec2<f32> {{
+    let z = {fold_z};
+    let dz = {fold_dz};
+    let square = complex_mul(z, z);
+    let derivative = 2.0 * complex_mul(z, dz);
+    return vec2<f32>({derivative_real}, {derivative_imag});
+}}
+
+fn formula_double_single(z: DsComplex) -> DsComplex {{
+    var real = z.real;
+    var imag = z.imag;
+{fold_real}
+    // (a + bi)^2 = (a + b)(a - b) + 2abi
+    let product = ds_mul(real, imag);
+    let square_real = ds_mul(ds_add(real, imag), ds_sub(real, imag));
+    let square_imag = ds_add(product, product);
+    return DsComplex({ds_real}, {ds_imag});
+}}
+",
+            fold_z = fold_z,
+            fold_dz = fold_dz,
+            square_real = part(0, "abs(square)", ""),
+            square_imag = part(1, "abs(square)", ""),
+            derivative_real = part(0, "derivative", "sign(square.x) * "),
+            derivative_imag = part(1, "derivative", "sign(square.y) * "),
+            fold_real = fold_real,
+            ds_real = ds_part(0, "square_real"),
+            ds_imag = ds_part(1, "square_imag"),
+        );
+        out
+    }
+}
+
+fn mul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+/// Folds `z` before it's squared.
+fn fold_z(fold: Fold, z: [f64; 2]) -> [f64; 2] {
+    match fold {
+        Fold::None => z,
+        Fold::Abs => [z[0].abs(), z[1].abs()],
+        Fold::Conjugate => [z[0], -z[1]],
+    }
+}
+
+/// Takes the absolute values of whichever parts of `value` are in `abs`, according to the signs of `square`'s,
+/// which is `square` itself for the formula and the square's derivative for the formula's derivative.
+fn fold_square(abs: [bool; 2], square: [f64; 2], value: [f64; 2]) -> [f64; 2] {
+    let mut out = value;
+    for i in 0..2 {
+        if abs[i] {
+            out[i] *= square[i].signum();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coloring::DEFAULT_BAILOUT;
+
+    const FORMULAS: [Formula; 6] = [
+        Formula::MANDELBROT,
+        Formula::Multibrot(Power(3)),
+        Formula::BurningShip,
+        Formula::Tricorn,
+        Formula::Celtic,
+        Formula::Buffalo,
+    ];
+
+    /// Points away from the axes and from where Re z² or Im z² are 0, where the folded formulas aren't differentiable.
+    const POINTS: [[f64; 2]; 4] = [[0.3, 0.7], [-1.1, 0.4], [-0.6, -1.3], [0.8, -0.2]];
+
+    #[test]
+    fn multibrot_power_is_validated() {
+        assert_eq!(Formula::multibrot(0), None);
+        assert_eq!(Formula::multibrot(1), None);
+        assert_eq!(Formula::multibrot(2), Some(Formula::MANDELBROT));
+        assert_eq!(Formula::multibrot(5).and_then(Formula::power), Some(5));
+    }
+
+    #[test]
+    fn apply_matches_definitions() {
+        for z in POINTS {
+            let [x, y] = z;
+            let square = [x * x - y * y, 2.0 * x * y];
+            let cube = mul(square, z);
+            let expected = [
+                (Formula::MANDELBROT, square),
+                (Formula::Multibrot(Power(3)), cube),
+                (
+                    Formula::BurningShip,
+                    mul([x.abs(), y.abs()], [x.abs(), y.abs()]),
+                ),
+                (Formula::Tricorn, [square[0], -square[1]]),
+                (Formula::Celtic, [square[0].abs(), square[1]]),
+                (Formula::Buffalo, [square[0].abs(), square[1].abs()]),
+            ];
+            for (formula, expected) in expected {
+                let w = formula.apply(z);
+                assert!(
+                    (w[0] - expected[0]).abs() < 1e-12 && (w[1] - expected[1]).abs() < 1e-12,
+                    "{:?} of {:?} gave {:?}, not {:?}",
+                    formula,
+                    z,
+                    w,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn derivative_matches_finite_differences() {
+        let h = 1e-7;
+        for formula in FORMULAS {
+            for z in POINTS {
+                for dz in [[1.0, 0.0], [0.0, 1.0], [0.6, -0.8]] {
+                    let derivative = formula.derivative(z, dz);
+                    let ahead = formula.apply([z[0] + h * dz[0], z[1] + h * dz[1]]);
+                    let behind = formula.apply([z[0] - h * dz[0], z[1] - h * dz[1]]);
+                    for i in 0..2 {
+                        let expected = (ahead[i] - behind[i]) / (2.0 * h);
+                        assert!(
+                            (derivative[i] - expected).abs() < 1e-5,
+                            "{:?} at {:?} along {:?} gave {:?}",
+                            formula,
+                            z,
+                            dz,
+                            derivative
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn iterate_matches_apply() {
+        let c = [-0.7, 0.6];
+        for formula in FORMULAS {
+            let mut z = [0.0, 0.0];
+            let mut iters = 0;
+            while iters < 1000
+                && z[0] * z[0] + z[1] * z[1] < (DEFAULT_BAILOUT * DEFAULT_BAILOUT) as f64
+            {
+                let w = formula.apply(z);
+                z = [w[0] + c[0], w[1] + c[1]];
+                iters += 1;
+            }
+            let pixel = formula.iterate(c, None, 1000, DEFAULT_BAILOUT, 1.0);
+            assert_eq!(pixel.iters, iters, "{:?}", formula);
+            assert_eq!(
+                pixel.norm,
+                (z[0] * z[0] + z[1] * z[1]) as f32,
+                "{:?}",
+                formula
+            );
+        }
+    }
+
+    #[test]
+    fn known_points() {
+        let iterations = 1000;
+        let iterate = |formula: Formula, c| {
+            formula
+                .iterate(c, None, iterations, DEFAULT_BAILOUT, 1.0)
+                .iters
+        };
+
+        for formula in FORMULAS {
+            assert_eq!(iterate(formula, [0.0, 0.0]), iterations, "{:?}", formula);
+            assert!(iterate(formula, [1.0, 0.0]) < iterations, "{:?}", formula);
+        }
+        // The real axis of the Burning Ship is the same as the Mandelbrot set's, which reaches out to -2.
+        assert_eq!(iterate(Formula::BurningShip, [-1.75, 0.0]), iterations);
+        assert!(iterate(Formula::BurningShip, [-2.1, 0.0]) < iterations);
+        // z³ + c has a superattracting 2-cycle at c = i (0 → i → 0), but 1.2i escapes.
+        assert_eq!(
+            iterate(Formula::Multibrot(Power(3)), [0.0, 1.0]),
+            iterations
+        );
+        assert!(iterate(Formula::Multibrot(Power(3)), [0.0, 1.2]) < iterations);
+    }
+}
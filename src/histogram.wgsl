@@ -0,0 +1,36 @@
+// Builds the cumulative histogram of escaped pixels' iteration counts for `COLORING_HISTOGRAM`.
+// `count_main` runs over every pixel, and then `sum_main` runs once to turn the counts into running totals.
+
+[[block]]
+struct Histogram {
+    bins: array<atomic<u32>, HISTOGRAM_BINS>;
+};
+
+[[group(0), binding(1)]] var<storage, read_write> pixels: Pixels;
+[[group(0), binding(7)]] var<storage, read_write> histogram: Histogram;
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn count_main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= settings.width || id.y >= settings.height) {
+        return;
+    }
+
+    let pixel = pixels.pixels[id.y * settings.width + id.x];
+    if (pixel.iters >= settings.iterations) {
+        return;
+    }
+
+    let bin = histogram_bin(smooth_iterations(pixel) / f32(settings.iterations));
+    // Atomics can't be called as statements, so the result has to go somewhere.
+    let count = atomicAdd(&histogram.bins[bin], 1u);
+}
+
+// There are few enough bins that adding them up in a single invocation is quicker than coordinating a parallel prefix sum.
+[[stage(compute), workgroup_size(1)]]
+fn sum_main() {
+    var total = 0u;
+    for (var i = 0u; i < HISTOGRAM_BINS; i = i + 1u) {
+        total = total + atomicLoad(&histogram.bins[i]);
+        atomicStore(&histogram.bins[i], total);
+    }
+}
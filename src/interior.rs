@@ -0,0 +1,232 @@
+//! Colouring the pixels inside the set, which would otherwise all just be black.
+//!
+//! Most of these need the attracting cycle the pixel's orbit falls into. Once its period p is known, the cycle can be
+//! pinned down with Newton's method on f^p(z) - z = 0. Its multiplier λ = d(f^p)/dz goes from 0 at the centre of the
+//! hyperbolic component to 1 on its edge. The distance to the edge can be estimated from the cycle's derivatives, like
+//! the exterior distance estimate:
+//!
+//! ```text
+//! d = (1 - |∂z|²) / |∂c∂z + ∂z∂z ∂c / (1 - ∂z)|
+//! ```
+//!
+//! Pixels only have a period if periodicity checking found one, which perturbation doesn't do.
+//! So only `Magnitude` works for every pixel; the others leave pixels black if their period isn't known.
+//!
+//! This mirrors `add_cycle` in `common.wgsl` (which measures the distance in pixels rather than on the complex plane)
+//! and the interior half of `colorize.wgsl`, so that images rendered on the CPU come out the same.
+
+use crate::palette::Palette;
+use crate::Pixel;
+
+/// How many plain iterations are done before Newton's method, to get closer to the cycle first.
+const CYCLE_WARMUP: u32 = 64;
+/// How many steps of Newton's method are used to find the cycle.
+const CYCLE_NEWTON_STEPS: u32 = 8;
+
+/// How many periods `InteriorColoring::Period` fits into the palette before it starts repeating.
+pub const PALETTE_PERIODS: f32 = 12.0;
+/// How many doublings of the distance `InteriorColoring::Distance` fits into the palette.
+pub const PALETTE_DISTANCE_OCTAVES: f32 = 16.0;
+
+/// How pixels in the set are coloured (the `INTERIOR_*` constants in `common.wgsl`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteriorColoring {
+    /// Leave them black.
+    None = 0,
+    /// By |z| at the end of the orbit, or of the attracting cycle if the period is known.
+    Magnitude = 1,
+    /// By the period of the attracting cycle, which gives each hyperbolic component a flat colour.
+    Period = 2,
+    /// By the estimated distance to the edge of the hyperbolic component.
+    Distance = 3,
+    /// By the magnitude of the attracting cycle's multiplier, which gives rings around each component's centre.
+    Multiplier = 4,
+}
+
+impl InteriorColoring {
+    /// Where `pixel`, which is in the set, lands in the interior palette before its offset and scale are applied,
+    /// or `None` if it should be left black.
+    pub fn value(self, pixel: &Pixel) -> Option<f32> {
+        match self {
+            Self::None => None,
+            Self::Magnitude => Some(pixel.norm.sqrt() / 2.0),
+            _ if pixel.period == 0 => None,
+            Self::Period => Some((pixel.period - 1) as f32 / PALETTE_PERIODS),
+            Self::Distance => Some((1.0 + pixel.distance).log2() / PALETTE_DISTANCE_OCTAVES),
+            Self::Multiplier => Some(pixel.multiplier[0].hypot(pixel.multiplier[1])),
+        }
+    }
+
+    /// The linear RGB colour of `pixel`, which is in the set, with `palette`.
+    pub fn color(self, pixel: &Pixel, palette: &Palette) -> [f32; 3] {
+        match self.value(pixel) {
+            Some(value) => palette.color(value),
+            None => [0.0; 3],
+        }
+    }
+}
+
+/// The attracting cycle of a point in the set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cycle {
+    /// A point on the cycle.
+    pub z: [f64; 2],
+    /// d(f^p)/dz around the cycle, whose magnitude goes from 0 at the centre of the component to 1 on its edge.
+    pub multiplier: [f64; 2],
+    /// The estimated distance from c to the edge of its hyperbolic component.
+    pub distance: f64,
+}
+
+impl Cycle {
+    /// Finds the cycle of period `period` which `c`'s orbit falls into, starting from `z`, which is somewhere along the orbit.
+    pub fn find(c: [f64; 2], z: [f64; 2], period: u32) -> Self {
+        let one = [1.0, 0.0];
+
+        let mut z = z;
+        for _ in 0..CYCLE_WARMUP {
+            z = add(mul(z, z), c);
+        }
+
+        for _ in 0..CYCLE_NEWTON_STEPS {
+            let mut w = z;
+            let mut dw = one;
+            for _ in 0..period {
+                dw = scale(mul(w, dw), 2.0);
+                w = add(mul(w, w), c);
+            }
+            // z' = z - (f^p(z) - z) / (d(f^p)/dz - 1)
+            z = sub(z, div(sub(w, z), sub(dw, one)));
+        }
+
+        let mut w = z;
+        let mut dz = one;
+        let mut dc = [0.0; 2];
+        let mut dzdz = [0.0; 2];
+        let mut dcdz = [0.0; 2];
+        for _ in 0..period {
+            dcdz = scale(add(mul(dc, dz), mul(w, dcdz)), 2.0);
+            dzdz = scale(add(mul(dz, dz), mul(w, dzdz)), 2.0);
+            dc = add(scale(mul(w, dc), 2.0), one);
+            dz = scale(mul(w, dz), 2.0);
+            w = add(mul(w, w), c);
+        }
+
+        let denominator = add(dcdz, div(mul(dzdz, dc), sub(one, dz)));
+        Self {
+            z,
+            multiplier: dz,
+            distance: (1.0 - norm_sqr(dz)) / norm_sqr(denominator).sqrt(),
+        }
+    }
+}
+
+fn add(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f64; 2], factor: f64) -> [f64; 2] {
+    [a[0] * factor, a[1] * factor]
+}
+
+fn mul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+fn div(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    let norm = norm_sqr(b);
+    [
+        (a[0] * b[0] + a[1] * b[1]) / norm,
+        (a[1] * b[0] - a[0] * b[1]) / norm,
+    ]
+}
+
+fn norm_sqr(a: [f64; 2]) -> f64 {
+    a[0] * a[0] + a[1] * a[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Finds the cycle of `c`, starting from z₁ = c.
+    fn cycle(c: [f64; 2], period: u32) -> Cycle {
+        Cycle::find(c, c, period)
+    }
+
+    #[test]
+    fn main_cardioid_multiplier() {
+        for &c in &[
+            [0.0, 0.0],
+            [0.1, 0.2],
+            [-0.5, 0.0],
+            [-0.6, 0.3],
+            [0.2, -0.4],
+        ] {
+            let cycle = cycle(c, 1);
+            let multiplier = norm_sqr(cycle.multiplier).sqrt();
+            assert!(multiplier < 1.0, "|λ| was {} for {:?}", multiplier, c);
+
+            // The fixed point is z = (1 - √(1 - 4c)) / 2, so λ = 2z = 1 - √(1 - 4c).
+            let w = [1.0 - 4.0 * c[0], -4.0 * c[1]];
+            let root = (norm_sqr(w).sqrt().sqrt(), w[1].atan2(w[0]) / 2.0);
+            let expected = sub([1.0, 0.0], [root.0 * root.1.cos(), root.0 * root.1.sin()]);
+            assert!(norm_sqr(sub(cycle.multiplier, expected)) < 1e-20);
+        }
+    }
+
+    #[test]
+    fn centers_are_superattracting() {
+        // The centres of the main cardioid and the period 2 and 3 components.
+        let centers = [
+            ([0.0, 0.0], 1),
+            ([-1.0, 0.0], 2),
+            ([-0.122_561_166_876_654, 0.744_861_766_619_744], 3),
+        ];
+        for &(c, period) in &centers {
+            let multiplier = norm_sqr(cycle(c, period).multiplier).sqrt();
+            assert!(multiplier < 1e-6, "|λ| was {} for {:?}", multiplier, c);
+        }
+    }
+
+    #[test]
+    fn period_2_bulb() {
+        // The period 2 bulb is the disc |c + 1| < 1/4, where λ = 4(c + 1).
+        let c = [-1.1, 0.05];
+        let multiplier = cycle(c, 2).multiplier;
+        assert!(norm_sqr(sub(multiplier, [4.0 * (c[0] + 1.0), 4.0 * c[1]])) < 1e-20);
+
+        // Its centre is 1/4 from the edge; like the exterior estimate, this is within a factor of 4.
+        let distance = cycle([-1.0, 0.0], 2).distance;
+        assert!(
+            (0.25 / 4.0..=0.25 * 4.0).contains(&distance),
+            "distance was {}",
+            distance
+        );
+    }
+
+    #[test]
+    fn values() {
+        let pixel = Pixel {
+            period: 3,
+            multiplier: [0.3, 0.4],
+            norm: 1.0,
+            ..Pixel::default()
+        };
+        assert_eq!(InteriorColoring::None.value(&pixel), None);
+        assert_eq!(InteriorColoring::Magnitude.value(&pixel), Some(0.5));
+        assert_eq!(
+            InteriorColoring::Period.value(&pixel),
+            Some(2.0 / PALETTE_PERIODS)
+        );
+        assert!((InteriorColoring::Multiplier.value(&pixel).unwrap() - 0.5).abs() < 1e-6);
+
+        // Without a period, only the magnitude can be shown.
+        let unknown = Pixel { period: 0, ..pixel };
+        assert_eq!(InteriorColoring::Multiplier.value(&unknown), None);
+        assert_eq!(InteriorColoring::Magnitude.value(&unknown), Some(0.5));
+    }
+}
@@ -0,0 +1,72 @@
+//! Picking the iteration limit automatically.
+//!
+//! Too few iterations and pixels which would have escaped eventually get coloured as part of the set, turning the boundary into black blobs;
+//! too many and time gets wasted on pixels which are really in the set.
+//!
+//! Deeper zooms need more iterations to resolve, so the zoom sets a minimum, and then the limit is adjusted each frame
+//! based on how many pixels hit it compared to how many only just escaped in time.
+//! If lots of pixels escaped close to the limit, there are probably more among the ones which hit it which would have escaped with a few more iterations.
+
+use bytemuck::Pod;
+use bytemuck::Zeroable;
+
+use crate::num::FloatExp;
+use crate::INITIAL_ZOOM;
+
+/// The iteration limit at the initial zoom.
+pub const MIN_ITERATIONS: u32 = 256;
+/// The most iterations the limit is ever raised to, which keeps the reference orbit and BLA table from growing forever.
+pub const MAX_ITERATIONS: u32 = 1 << 18;
+/// How many extra iterations are needed for every doubling of the zoom.
+const ITERATIONS_PER_OCTAVE: f64 = 64.0;
+
+/// The fraction of the limit past which a pixel counts as having escaped close to it.
+const NEAR_LIMIT: f64 = 0.75;
+/// If there are more pixels that escaped close to the limit than this fraction of the pixels which hit it, the limit is raised.
+const RAISE_RATIO: f64 = 0.05;
+/// If there are no more pixels that escaped close to the limit than this fraction of the pixels which hit it or escaped close to it, the limit is lowered.
+const LOWER_RATIO: f64 = 0.005;
+
+/// Counts of how many pixels ended up near the iteration limit in a frame, as written by `stats.wgsl`.
+#[derive(Clone, Copy, Zeroable, Pod, Debug)]
+#[repr(C)]
+pub struct IterationStats {
+    /// The number of pixels which hit the limit without escaping.
+    pub limited: u32,
+    /// The number of pixels which escaped after `near_limit` iterations.
+    pub near_limit: u32,
+}
+
+impl IterationStats {
+    pub const EMPTY: Self = Self {
+        limited: 0,
+        near_limit: 0,
+    };
+}
+
+/// The iteration count pixels need to have escaped after to count towards `IterationStats::near_limit`.
+pub fn near_limit(iterations: u32) -> u32 {
+    (iterations as f64 * NEAR_LIMIT) as u32
+}
+
+/// The smallest iteration limit to use at `zoom`.
+pub fn for_zoom(zoom: FloatExp) -> u32 {
+    let octaves = (zoom.log2() - (INITIAL_ZOOM as f64).log2()).max(0.0);
+    (MIN_ITERATIONS as f64 + octaves * ITERATIONS_PER_OCTAVE).min(MAX_ITERATIONS as f64) as u32
+}
+
+/// Picks the next iteration limit after a frame rendered with `iterations` came out with `stats`, never going below `minimum`.
+pub fn adjust(iterations: u32, stats: IterationStats, minimum: u32) -> u32 {
+    let limited = stats.limited as f64;
+    let near_limit = stats.near_limit as f64;
+
+    let next = if stats.limited > 0 && near_limit > RAISE_RATIO * limited {
+        iterations.saturating_mul(3) / 2
+    } else if near_limit <= LOWER_RATIO * (limited + near_limit) {
+        iterations / 4 * 3
+    } else {
+        iterations
+    };
+
+    next.clamp(minimum, MAX_ITERATIONS)
+}
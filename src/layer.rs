@@ -0,0 +1,169 @@
+//! Stacks of colourings composited on top of each other, like layers in Ultra Fractal.
+//!
+//! The main colouring settings make up the bottom layer, and then each `Layer` gets blended onto the result in turn,
+//! with its own colouring, palette, opacity, blend mode and lighting. Layers leave the inside of the set alone unless
+//! they're domain colourings, since nothing else has a value there.
+//!
+//! This mirrors `fs_main` in `colorize.wgsl`, so that images rendered on the CPU come out the same.
+
+use bytemuck::Pod;
+use bytemuck::Zeroable;
+
+use crate::coloring::Coloring;
+use crate::coloring::Histogram;
+use crate::coloring::Lighting;
+use crate::palette::Palette;
+use crate::Pixel;
+
+/// The most layers there can be on top of the main colouring (`MAX_LAYERS` in `colorize.wgsl`).
+pub const MAX_LAYERS: usize = 8;
+
+/// How a layer gets combined with the ones below it (the `BLEND_*` constants in `colorize.wgsl`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blend {
+    /// Replace what's below.
+    Normal = 0,
+    /// Multiply with what's below, which can only make it darker.
+    Multiply = 1,
+    /// The inverse of multiplying the inverses, which can only make it lighter.
+    Screen = 2,
+    /// Multiply the dark parts of what's below and screen the light parts, which adds contrast.
+    Overlay = 3,
+}
+
+impl Blend {
+    /// Blends `above` onto `below`, both in linear RGB.
+    pub fn apply(self, below: [f32; 3], above: [f32; 3]) -> [f32; 3] {
+        let mut out = [0.0; 3];
+        for i in 0..3 {
+            let (a, b) = (below[i], above[i]);
+            out[i] = match self {
+                Self::Normal => b,
+                Self::Multiply => a * b,
+                Self::Screen => 1.0 - (1.0 - a) * (1.0 - b),
+                Self::Overlay if a < 0.5 => 2.0 * a * b,
+                Self::Overlay => 1.0 - 2.0 * (1.0 - a) * (1.0 - b),
+            };
+        }
+        out
+    }
+}
+
+/// A colouring which gets composited over the ones below it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub coloring: Coloring,
+    pub palette: Palette,
+    /// How much of the blended colour is used, from 0 (none, leaving what's below) to 1.
+    pub opacity: f32,
+    pub blend: Blend,
+    /// The light the layer is lit with like a height field, if any.
+    pub lighting: Option<Lighting>,
+}
+
+impl Layer {
+    /// Creates an opaque, unlit layer with the normal blend mode.
+    pub fn new(coloring: Coloring, palette: Palette) -> Self {
+        Self {
+            coloring,
+            palette,
+            opacity: 1.0,
+            blend: Blend::Normal,
+            lighting: None,
+        }
+    }
+
+    /// Composites this layer for `pixel` onto `below`, the linear RGB colour of the layers under it.
+    pub fn composite(
+        &self,
+        below: [f32; 3],
+        pixel: &Pixel,
+        iterations: u32,
+        bailout: f32,
+        log_degree: f32,
+        histogram: Option<&Histogram>,
+    ) -> [f32; 3] {
+        if self
+            .coloring
+            .value(pixel, iterations, bailout, log_degree, histogram)
+            .is_none()
+        {
+            return below;
+        }
+
+        let above = self.coloring.color(
+            pixel,
+            iterations,
+            bailout,
+            log_degree,
+            &self.palette,
+            histogram,
+            self.lighting.as_ref(),
+        );
+        let blended = self.blend.apply(below, above);
+
+        let mut out = [0.0; 3];
+        for i in 0..3 {
+            out[i] = below[i] + (blended[i] - below[i]) * self.opacity;
+        }
+        out
+    }
+}
+
+/// A `Layer` in the form the GPU expects (`Layer` in `colorize.wgsl`), without its palette.
+#[derive(Clone, Copy, Zeroable, Pod, Debug)]
+#[repr(C)]
+struct GpuLayer {
+    coloring: u32,
+    blend: u32,
+    wrap: u32,
+    lighting: u32,
+    opacity: f32,
+    palette_offset: f32,
+    palette_scale: f32,
+    light_angle: f32,
+    light_elevation: f32,
+    ambient: f32,
+    specular: f32,
+    slope_depth: f32,
+}
+
+impl From<&Layer> for GpuLayer {
+    fn from(layer: &Layer) -> Self {
+        let lighting = layer.lighting.unwrap_or_default();
+        Self {
+            coloring: layer.coloring as u32,
+            blend: layer.blend as u32,
+            wrap: layer.palette.wrap as u32,
+            lighting: layer.lighting.is_some() as u32,
+            opacity: layer.opacity,
+            palette_offset: layer.palette.offset,
+            palette_scale: layer.palette.scale,
+            light_angle: lighting.angle,
+            light_elevation: lighting.elevation,
+            ambient: lighting.ambient,
+            specular: lighting.specular,
+            slope_depth: lighting.depth,
+        }
+    }
+}
+
+/// The whole stack of layers in the form the GPU expects (`Layers` in `colorize.wgsl`).
+#[derive(Clone, Copy, Zeroable, Pod, Debug)]
+#[repr(C)]
+pub struct GpuLayers {
+    count: u32,
+    layers: [GpuLayer; MAX_LAYERS],
+}
+
+impl GpuLayers {
+    /// Converts `layers`, which can't be any more than `MAX_LAYERS` long.
+    pub fn new(layers: &[Layer]) -> Self {
+        let mut out = Self::zeroed();
+        out.count = layers.len() as u32;
+        for (gpu_layer, layer) in out.layers.iter_mut().zip(layers) {
+            *gpu_layer = GpuLayer::from(layer);
+        }
+        out
+    }
+}
@@ -1,18 +1,57 @@
-#![feature(bigint_helper_methods)]
-
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::iter;
+use std::mem;
 use std::mem::size_of;
+use std::num::NonZeroU32;
 use std::num::NonZeroU64;
 
+use bla::BlaTable;
+use bla::GpuBla;
 use bytemuck::Pod;
 use bytemuck::Zeroable;
+use coloring::Coloring;
+use coloring::Lighting;
+use coloring::DEFAULT_BAILOUT;
+use coloring::HISTOGRAM_BINS;
+use coloring::MAX_BAILOUT;
+use formula::Formula;
+use interior::InteriorColoring;
+use iterations::IterationStats;
+use layer::GpuLayers;
+use layer::Layer;
 use num::Complex;
-use wgpu::include_wgsl;
+use num::Component;
+use num::DoubleSingle;
+use num::FloatExp;
+use orbit::OrbitTrap;
+use orbit::DEFAULT_STRIPE_FREQUENCY;
+use palette::Cycling;
+use palette::Palette;
+use palette::Wrap;
+use perturbation::Glitches;
+use perturbation::ReferenceOrbit;
+use perturbation::MAX_SECONDARY_REFERENCES;
+use precision::Precision;
+use precision::MAX_FIXED_POINT_LIMBS;
+use series::SeriesApproximation;
+use supersampling::SamplePass;
+use supersampling::SamplePixels;
+use supersampling::Supersampling;
+use supersampling::DEFAULT_EDGE_THRESHOLD;
+use supersampling::MAX_ACCUMULATED_FRAMES;
+use viewport::Viewport;
+use viewport::MAX_PREVIEW_ITERATIONS;
+use viewport::PREVIEW_SCALE;
 use wgpu::util::BufferInitDescriptor;
 use wgpu::util::DeviceExt;
+use wgpu::AddressMode;
+use wgpu::BindGroup;
 use wgpu::BindGroupDescriptor;
 use wgpu::BindGroupEntry;
+use wgpu::BindGroupLayout;
 use wgpu::BindGroupLayoutDescriptor;
 use wgpu::BindGroupLayoutEntry;
 use wgpu::BindingResource;
@@ -20,14 +59,24 @@ use wgpu::BindingType;
 use wgpu::Buffer;
 use wgpu::BufferBinding;
 use wgpu::BufferBindingType;
+use wgpu::BufferDescriptor;
 use wgpu::BufferUsages;
 use wgpu::Color;
 use wgpu::CommandEncoderDescriptor;
+use wgpu::ComputePassDescriptor;
+use wgpu::ComputePipeline;
+use wgpu::ComputePipelineDescriptor;
 use wgpu::Device;
 use wgpu::DeviceDescriptor;
+use wgpu::Extent3d;
+use wgpu::FilterMode;
 use wgpu::FragmentState;
+use wgpu::ImageCopyTexture;
+use wgpu::ImageDataLayout;
 use wgpu::LoadOp;
 use wgpu::Operations;
+use wgpu::Origin3d;
+use wgpu::PipelineLayout;
 use wgpu::PipelineLayoutDescriptor;
 use wgpu::PresentMode;
 use wgpu::PrimitiveState;
@@ -38,32 +87,244 @@ use wgpu::RenderBundleDescriptor;
 use wgpu::RenderBundleEncoderDescriptor;
 use wgpu::RenderPassColorAttachment;
 use wgpu::RenderPassDescriptor;
+use wgpu::RenderPipeline;
 use wgpu::RenderPipelineDescriptor;
 use wgpu::RequestAdapterOptions;
+use wgpu::Sampler;
+use wgpu::SamplerDescriptor;
+use wgpu::ShaderModuleDescriptor;
+use wgpu::ShaderSource;
 use wgpu::ShaderStages;
 use wgpu::Surface;
 use wgpu::SurfaceConfiguration;
+use wgpu::Texture;
+use wgpu::TextureAspect;
+use wgpu::TextureDescriptor;
+use wgpu::TextureDimension;
 use wgpu::TextureFormat;
+use wgpu::TextureSampleType;
 use wgpu::TextureUsages;
 use wgpu::TextureViewDescriptor;
+use wgpu::TextureViewDimension;
 use wgpu::VertexState;
 use winit::window::Window;
 
+pub mod bla;
+pub mod coloring;
+pub mod formula;
+pub mod interior;
+pub mod iterations;
+pub mod layer;
 pub mod num;
+pub mod orbit;
+pub mod output;
+pub mod palette;
+pub mod perturbation;
+pub mod precision;
+pub mod series;
+pub mod supersampling;
+pub mod viewport;
 
-const ITERATIONS: u32 = 1200;
 // The mandelbrot set ranges from -2 to 2, so multiplying that by 150 makes it take up a 600x600 space initially.
 pub const INITIAL_ZOOM: f32 = 150.0;
 
+/// How many storage buffers the iteration bind group has (bindings 1 to 7), which is more than the downlevel defaults allow.
+const ITERATE_STORAGE_BUFFERS: u32 = 7;
+
+/// The most room in bytes the samples buffer can take up.
+/// The passes of frames which need more than that aren't kept, so recolouring them means iterating them again.
+const MAX_SAMPLES_SIZE: u64 = 512 << 20;
+
+/// The width and height of the iteration shader's workgroups.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// The stages `colorize.wgsl`'s bindings are used in: `accumulate_main` and `fs_main`.
+const COLORIZE_STAGES: ShaderStages =
+    ShaderStages::from_bits_truncate(ShaderStages::COMPUTE.bits() | ShaderStages::FRAGMENT.bits());
+
+/// Like `include_wgsl!`, but prepends the declarations in `common.wgsl`, and any other files listed after the main one.
+macro_rules! include_shader {
+    ($file:literal $(, $dependency:literal)*) => {
+        ShaderModuleDescriptor {
+            label: Some($file),
+            source: ShaderSource::Wgsl(Cow::Borrowed(concat!(
+                include_str!("common.wgsl"),
+                $(include_str!($dependency),)*
+                include_str!($file)
+            ))),
+        }
+    };
+}
+
 #[derive(Clone, Copy, Zeroable, Pod, Debug)]
 #[repr(C)]
 pub struct Settings {
     center: [f32; 2],
+    camera: [f32; 2],
+    camera_low: [f32; 2],
+    reference_offset: [f32; 2],
+
+    series: [[f32; 2]; 3],
+
+    width: u32,
+    height: u32,
 
     iterations: u32,
+    pixel_size: f32,
+    pixel_size_exponent: i32,
 
-    camera: [f32; 2],
-    zoom: f32,
+    reference_len: u32,
+    secondary: u32,
+
+    series_skip: u32,
+    series_exponents: [i32; 3],
+
+    bla_levels: u32,
+
+    near_limit: u32,
+    period_tolerance: f32,
+
+    bailout: f32,
+    coloring: u32,
+
+    palette_offset: f32,
+    palette_scale: f32,
+
+    lighting: u32,
+    light_angle: f32,
+    light_elevation: f32,
+    ambient: f32,
+    specular: f32,
+    slope_depth: f32,
+
+    trap_center: [f32; 2],
+    trap_shape: u32,
+    trap_angle: f32,
+    trap_radius: f32,
+    stripe_frequency: f32,
+
+    interior_coloring: u32,
+    interior_palette_offset: f32,
+    interior_palette_scale: f32,
+
+    orbit_tracking: u32,
+
+    encode_srgb: u32,
+    dither_step: f32,
+
+    sample_offset: [f32; 2],
+    sample_pixels: u32,
+    accumulate: u32,
+    edge_threshold: u32,
+
+    julia: u32,
+    julia_c: [f32; 2],
+    julia_c_low: [f32; 2],
+
+    origin: [u32; 2],
+
+    log_degree: f32,
+    /// WGSL rounds the struct's size up to a multiple of 8 bytes, since that's what its `vec2`s are aligned to.
+    padding: u32,
+}
+
+/// Everything that decides what the image looks like, so that frames can tell whether they're of the same one.
+#[derive(Debug)]
+struct Image {
+    settings: Settings,
+    supersampling: Option<Supersampling>,
+    /// The settings only have the camera and the Julia set's parameter as double-singles, which stop changing
+    /// long before the real things do once it's zoomed in far enough for fixed point or perturbation, so these get compared in full.
+    camera: Complex,
+    zoom: FloatExp,
+    julia_c: Option<Complex>,
+    formula: Formula,
+}
+
+impl Image {
+    /// Whether `other` is the same image.
+    fn matches(&self, other: &Self) -> bool {
+        bytemuck::bytes_of(&self.settings) == bytemuck::bytes_of(&other.settings)
+            && self.same_pixels(other)
+    }
+
+    /// Whether `other` has the same pixels, even if they're coloured differently.
+    fn same_pixels(&self, other: &Self) -> bool {
+        bytemuck::bytes_of(&self.settings)
+            == bytemuck::bytes_of(&other.settings.with_colors_of(&self.settings))
+            && self.supersampling == other.supersampling
+            && self.camera == other.camera
+            && self.zoom == other.zoom
+            && self.julia_c == other.julia_c
+            && self.formula == other.formula
+    }
+}
+
+/// What the accumulation buffer was last rendered with, so that frames can keep adding to it until something changes.
+#[derive(Debug)]
+struct Accumulated {
+    image: Image,
+    /// How many frames have added samples to it,
+    /// or 0 if a secondary reference was picked while rendering it and it needs rendering again from scratch.
+    frames: u32,
+    /// The passes of the first frame, whose pixels are kept in the samples buffer in the same order,
+    /// or nothing if there were too many to keep (see `MAX_SAMPLES_SIZE`).
+    passes: Vec<SamplePass>,
+    /// The point whose Julia set the preview was iterated for.
+    preview_c: Complex,
+}
+
+impl Settings {
+    /// These settings with the ones which only affect how pixels are coloured, not how they're iterated, taken from `other`.
+    fn with_colors_of(self, other: &Self) -> Self {
+        Self {
+            palette_offset: other.palette_offset,
+            palette_scale: other.palette_scale,
+            interior_palette_offset: other.interior_palette_offset,
+            interior_palette_scale: other.interior_palette_scale,
+            ..self
+        }
+    }
+
+    /// Sets which sample of which pixels these settings iterate and colour.
+    fn for_pass(self, pass: &SamplePass) -> Self {
+        Self {
+            sample_offset: pass.offset,
+            sample_pixels: pass.pixels as u32,
+            accumulate: pass.accumulate as u32,
+            ..self
+        }
+    }
+}
+
+/// The result of iterating a single pixel (`Pixel` in `common.wgsl`).
+#[derive(Clone, Copy, Zeroable, Pod, Debug, Default)]
+#[repr(C)]
+pub struct Pixel {
+    pub iters: u32,
+    pub flags: u32,
+    /// The period of the cycle the pixel's orbit fell into, if it was found to be in the set that way, or 0 otherwise.
+    pub period: u32,
+    /// |z|² after the last iteration, or of the attracting cycle if it's in the set, its period is known and there's interior colouring.
+    pub norm: f32,
+    /// The gradient of the smooth iteration count in pixels, for lighting it like a height field, or 0 if it's in the set.
+    pub slope: [f32; 2],
+    /// The estimated distance from the pixel to the set, in pixels.
+    ///
+    /// If it's in the set, this is instead the distance to the edge of its hyperbolic component if the attracting cycle was found, or 0 if not.
+    pub distance: f32,
+    /// The closest the pixel's orbit got to the orbit trap, if it's being tracked.
+    pub trap: f32,
+    /// The pixel's stripe average, if it's being tracked and the pixel escaped.
+    pub stripe: f32,
+    /// The argument of z after the last iteration, between -π and π, or 0 if it's in the set.
+    pub angle: f32,
+    /// The attracting cycle's multiplier if it's in the set and the cycle was found, or 0 otherwise.
+    pub multiplier: [f32; 2],
+    /// The pixel's atom domain, if it's being tracked.
+    pub atom: u32,
+    /// The pixel's Misiurewicz domain, if it's being tracked.
+    pub misiurewicz: u32,
 }
 
 #[derive(Debug)]
@@ -72,13 +333,116 @@ pub struct State {
     pub queue: Queue,
     pub surface: Surface,
 
-    pub settings_buffer: Buffer,
-    pub render_bundle: RenderBundle,
+    pub orbit_buffer: Buffer,
+    pub glitch_buffer: Buffer,
+    /// The BLA table for the current reference orbit.
+    pub bla_buffer: Buffer,
+    /// Counts of how many pixels hit the iteration limit or came close to it, for picking the limit automatically.
+    pub stats_buffer: Buffer,
+    /// A copy of `stats_buffer` which can be read back by the CPU.
+    pub stats_readback_buffer: Buffer,
+    /// The camera's position, the pixel size and the Julia set's c as fixed-point numbers, for when `precision` is `FixedPoint`.
+    pub fixed_point_buffer: Buffer,
+    /// A copy of `glitch_buffer` which can be read back by the CPU.
+    pub glitch_readback_buffer: Buffer,
+    /// `layers` in the form `colorize.wgsl` expects.
+    pub layers_buffer: Buffer,
+
+    /// The pipelines for each precision and formula we've used so far, which are created as they're needed.
+    pub iterate_pipelines: HashMap<(Precision, Formula), ComputePipeline>,
+    pub iterate_pipeline_layout: PipelineLayout,
+    pub iterate_bind_group_layout: BindGroupLayout,
+    pub stats_pipeline: ComputePipeline,
+    /// Counts how many pixels are in each bin of the histogram.
+    pub histogram_pipeline: ComputePipeline,
+    /// Turns the histogram's counts into running totals.
+    pub histogram_sum_pipeline: ComputePipeline,
+    /// Marks the pixels which adaptive supersampling supersamples.
+    pub edges_pipeline: ComputePipeline,
+
+    /// Adds each pass's colours to the accumulation buffer.
+    pub accumulate_pipeline: ComputePipeline,
+    pub colorize_pipeline: RenderPipeline,
+    pub colorize_bind_group_layout: BindGroupLayout,
     pub swapchain_format: TextureFormat,
+    /// `palette` baked into a 1D texture.
+    pub palette_texture: Texture,
+    /// Samples `palette_texture` according to `palette.wrap`.
+    pub palette_sampler: Sampler,
+    /// The same for `interior_palette`.
+    pub interior_palette_texture: Texture,
+    pub interior_palette_sampler: Sampler,
+    /// Each of `layers`' palettes baked into a row of a 2D texture, and a sampler which leaves wrapping them to the shader.
+    pub layer_palette_texture: Texture,
+    pub layer_palette_sampler: Sampler,
+
+    /// The view of the Mandelbrot or Julia set filling the whole window.
+    pub view: Viewport,
+    /// The inset in the corner of the window previewing the Julia set of `preview_c` while the Mandelbrot set is shown, if it's turned on.
+    pub julia_preview: Option<Viewport>,
+    /// The point under the cursor, whose Julia set the preview shows.
+    pub preview_c: Complex,
 
     // It's easier to keep a copy of these externally than read them from GPU memory every time.
     pub camera: Complex,
-    pub zoom: f32,
+    pub zoom: FloatExp,
+    /// The arithmetic pixels are currently being iterated with, which is picked based on the zoom.
+    pub precision: Precision,
+    /// The orbit of the point at the center of the screen, which every pixel starts off being rendered relative to
+    /// when `precision` is `Perturbation`.
+    pub reference: Option<ReferenceOrbit>,
+    /// The secondary reference orbits picked so far for fixing the glitches `reference` leaves, along with their offsets in pixels from it.
+    /// They're reused for every pass until the camera moves.
+    pub secondary_references: Vec<(ReferenceOrbit, [f32; 2])>,
+
+    /// The formula pixels are iterated with; use `set_formula` to change it.
+    pub formula: Formula,
+    /// Whether the Julia set of `julia_c` is being shown instead of the Mandelbrot set.
+    pub julia: bool,
+    /// The parameter of the Julia set last picked with `pick_julia`, which is kept while the Mandelbrot set is shown.
+    pub julia_c: Option<Complex>,
+    /// The camera's position and zoom in whichever of the Mandelbrot and Julia sets isn't being shown, for switching back to it.
+    pub other_camera: Complex,
+    pub other_zoom: FloatExp,
+
+    /// The maximum number of iterations before a pixel is assumed to be in the set.
+    pub iterations: u32,
+    /// Whether `iterations` is being picked automatically, based on the zoom and how many pixels hit the limit.
+    pub auto_iterations: bool,
+    /// The number of iterations the reference orbit and BLA buffers currently have room for.
+    pub iteration_capacity: u32,
+
+    pub coloring: Coloring,
+    /// How far z has to get from the origin to count as escaped; use `set_bailout` to change it.
+    pub bailout: f32,
+    /// The colours escaped pixels are given; use `set_palette` to change anything but `offset` and `scale`.
+    pub palette: Palette,
+    /// The light the image is lit with like a height field, if any.
+    pub lighting: Option<Lighting>,
+    /// The trap pixels are coloured by how close their orbits get to with `Coloring::Trap`.
+    pub trap: OrbitTrap,
+    /// How many stripes there are per turn around the origin with `Coloring::Stripes`.
+    pub stripe_frequency: f32,
+    pub interior_coloring: InteriorColoring,
+    /// The colours pixels in the set are given; use `set_interior_palette` to change anything but `offset` and `scale`.
+    pub interior_palette: Palette,
+    /// The layers composited on top of the main colouring; use `set_layers` to change them.
+    pub layers: Vec<Layer>,
+    /// How the palette's offset moves over time, if it does; see `cycle_palette`.
+    pub palette_cycling: Option<Cycling>,
+    /// Whether the output gets ordered dithered, to hide the banding from only having 8 bits per channel.
+    pub dithering: bool,
+    /// Whether extra samples keep getting added to the image while the view stays still (see `supersampling.rs`).
+    pub temporal_accumulation: bool,
+    /// What's in the accumulation buffer, if anything.
+    accumulated: Option<Accumulated>,
+    /// Copies of the main view's pixel buffer after each pass of the last frame rendered from scratch,
+    /// so that `recolor` can colour them again without iterating anything, as long as they fit in `MAX_SAMPLES_SIZE`.
+    pub samples_buffer: Buffer,
+    /// How many pixel buffers' worth of room `samples_buffer` has.
+    pub samples_capacity: u32,
+    /// Where extra samples are taken within each pixel to smooth out aliasing, if anywhere.
+    pub supersampling: Option<Supersampling>,
 }
 
 impl State {
@@ -96,13 +460,28 @@ impl State {
             .await
             .expect("Failed to find an appropriate adapter");
 
+        let adapter_limits = adapter.limits();
+        assert!(
+            adapter_limits.max_storage_buffers_per_shader_stage >= ITERATE_STORAGE_BUFFERS,
+            "The adapter only supports {} storage buffers per shader stage, but iterating needs {}",
+            adapter_limits.max_storage_buffers_per_shader_stage,
+            ITERATE_STORAGE_BUFFERS
+        );
+
         let (device, queue) = adapter
             .request_device(
                 &DeviceDescriptor {
                     label: None,
                     features: wgpu::Features::empty(),
                     // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
-                    limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+                    // The pixel buffer also needs as big a binding as the adapter can give it (see `view_rect`).
+                    limits: wgpu::Limits {
+                        max_storage_buffers_per_shader_stage: ITERATE_STORAGE_BUFFERS,
+                        max_storage_buffer_binding_size: adapter_limits
+                            .max_storage_buffer_binding_size,
+                        ..wgpu::Limits::downlevel_defaults()
+                            .using_resolution(adapter_limits.clone())
+                    },
                 },
                 None,
             )
@@ -110,58 +489,166 @@ impl State {
             .expect("Failed to obtain device");
 
         let size = window.inner_size();
+        let [view_x, view_y, view_width, view_height] = view_rect(
+            size.width,
+            size.height,
+            device.limits().max_storage_buffer_binding_size,
+        );
 
-        let settings_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("Settings buffer"),
-            contents: bytemuck::bytes_of(&Settings {
-                center: [size.width as f32 / 2.0, size.height as f32 / 2.0],
+        let settings_buffer = create_settings_buffer(&device);
 
-                camera: [0.0, 0.0],
-                zoom: INITIAL_ZOOM,
+        let pixel_buffer = create_pixel_buffer(&device, view_width, view_height);
+        let samples_buffer = create_samples_buffer(&device, view_width, view_height, 1);
 
-                iterations: ITERATIONS,
-            }),
-            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        let iterations = iterations::for_zoom(FloatExp::from(INITIAL_ZOOM as f64));
+        let orbit_buffer = create_orbit_buffer(&device, iterations);
+
+        let glitch_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Glitch buffer"),
+            contents: bytemuck::bytes_of(&Glitches::EMPTY),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
         });
 
-        let settings_bind_group_layout =
+        let bla_buffer = create_bla_buffer(&device, iterations);
+
+        let stats_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Iteration stats buffer"),
+            contents: bytemuck::bytes_of(&IterationStats::EMPTY),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+        });
+
+        let stats_readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Iteration stats readback buffer"),
+            size: size_of::<IterationStats>() as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let fixed_point_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Fixed point buffer"),
+            // The real and imaginary parts of the camera and the Julia set's c plus the pixel size, each with an integer limb.
+            size: 5 * (MAX_FIXED_POINT_LIMBS as u64 + 1) * size_of::<u32>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let glitch_readback_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Glitch readback buffer"),
+            size: size_of::<Glitches>() as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let histogram_buffer = create_histogram_buffer(&device);
+
+        let layers_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Layers buffer"),
+            contents: bytemuck::bytes_of(&GpuLayers::new(&[])),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        });
+
+        let accumulation_buffer = create_accumulation_buffer(&device, view_width, view_height);
+
+        let iterate_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                label: Some("Settings bind group layout"),
-                entries: &[BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: NonZeroU64::new(size_of::<Settings>() as u64),
-                    },
-                    count: None,
-                }],
+                label: Some("Iterate bind group layout"),
+                entries: &[
+                    settings_layout_entry(ShaderStages::COMPUTE),
+                    storage_layout_entry(1, ShaderStages::COMPUTE, false),
+                    storage_layout_entry(2, ShaderStages::COMPUTE, true),
+                    storage_layout_entry(3, ShaderStages::COMPUTE, false),
+                    storage_layout_entry(4, ShaderStages::COMPUTE, true),
+                    storage_layout_entry(5, ShaderStages::COMPUTE, true),
+                    storage_layout_entry(6, ShaderStages::COMPUTE, false),
+                    storage_layout_entry(7, ShaderStages::COMPUTE, false),
+                ],
             });
 
-        let settings_bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: Some("Settings bind group"),
-            layout: &settings_bind_group_layout,
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: BindingResource::Buffer(BufferBinding {
-                    buffer: &settings_buffer,
-                    offset: 0,
-                    size: None,
-                }),
-            }],
+        let iterate_bind_group = create_iterate_bind_group(
+            &device,
+            &iterate_bind_group_layout,
+            &[
+                &settings_buffer,
+                &pixel_buffer,
+                &orbit_buffer,
+                &glitch_buffer,
+                &fixed_point_buffer,
+                &bla_buffer,
+                &stats_buffer,
+                &histogram_buffer,
+            ],
+        );
+
+        let iterate_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Iterate pipeline layout"),
+            bind_group_layouts: &[&iterate_bind_group_layout],
+            push_constant_ranges: &[],
         });
 
+        let stats_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Iteration stats pipeline"),
+            layout: Some(&iterate_pipeline_layout),
+            module: &device.create_shader_module(&include_shader!("stats.wgsl")),
+            entry_point: "main",
+        });
+
+        let histogram_shader = device.create_shader_module(&include_shader!("histogram.wgsl"));
+        let histogram_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Histogram pipeline"),
+            layout: Some(&iterate_pipeline_layout),
+            module: &histogram_shader,
+            entry_point: "count_main",
+        });
+        let histogram_sum_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Histogram sum pipeline"),
+            layout: Some(&iterate_pipeline_layout),
+            module: &histogram_shader,
+            entry_point: "sum_main",
+        });
+
+        let edges_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Edges pipeline"),
+            layout: Some(&iterate_pipeline_layout),
+            module: &device.create_shader_module(&include_shader!("edges.wgsl")),
+            entry_point: "main",
+        });
+
+        let colorize_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Colorize bind group layout"),
+                entries: &[
+                    settings_layout_entry(COLORIZE_STAGES),
+                    storage_layout_entry(1, COLORIZE_STAGES, true),
+                    storage_layout_entry(2, COLORIZE_STAGES, true),
+                    storage_layout_entry(3, COLORIZE_STAGES, true),
+                    storage_layout_entry(4, COLORIZE_STAGES, false),
+                    palette_texture_layout_entry(5, TextureViewDimension::D1),
+                    palette_sampler_layout_entry(6),
+                    palette_texture_layout_entry(7, TextureViewDimension::D1),
+                    palette_sampler_layout_entry(8),
+                    palette_texture_layout_entry(9, TextureViewDimension::D2),
+                    palette_sampler_layout_entry(10),
+                ],
+            });
+
         let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Render pipeline layout"),
-            bind_group_layouts: &[&settings_bind_group_layout],
+            bind_group_layouts: &[&colorize_bind_group_layout],
             push_constant_ranges: &[],
         });
 
-        let shader = device.create_shader_module(&include_wgsl!("shader.wgsl"));
+        let shader = device.create_shader_module(&include_shader!("colorize.wgsl"));
+
+        let accumulate_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Accumulate pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "accumulate_main",
+        });
+        // This is usually an `*Srgb` format natively but not on the web, which `fs_main` takes into account (see `output.rs`).
         let swapchain_format = surface.get_preferred_format(&adapter).unwrap();
 
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        let colorize_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
             label: Some("Render pipeline"),
             layout: Some(&pipeline_layout),
             vertex: VertexState {
@@ -182,21 +669,44 @@ impl State {
             }),
         });
 
-        let mut render_bundle_encoder =
-            device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
-                label: Some("Render bundle encoder"),
-                color_formats: &[swapchain_format],
-                depth_stencil: None,
-                sample_count: 1,
-            });
+        let palette = Palette::default();
+        let palette_texture = create_palette_texture(&device, "Palette texture", 1);
+        write_palette(&queue, &palette_texture, 0, &palette);
+        let palette_sampler = create_palette_sampler(&device, palette.wrap);
 
-        render_bundle_encoder.set_pipeline(&render_pipeline);
-        render_bundle_encoder.set_bind_group(0, &settings_bind_group, &[]);
-        render_bundle_encoder.draw(0..4, 0..1);
+        let interior_palette = Palette::interior();
+        let interior_palette_texture =
+            create_palette_texture(&device, "Interior palette texture", 1);
+        write_palette(&queue, &interior_palette_texture, 0, &interior_palette);
+        let interior_palette_sampler = create_palette_sampler(&device, interior_palette.wrap);
 
-        let render_bundle = render_bundle_encoder.finish(&RenderBundleDescriptor {
-            label: Some("Render bundle"),
-        });
+        let layer_palette_texture =
+            create_palette_texture(&device, "Layer palette texture", layer::MAX_LAYERS as u32);
+        // Each layer wraps its palette differently, so the shader does it itself.
+        let layer_palette_sampler = create_palette_sampler(&device, Wrap::Clamp);
+
+        let colorize_bind_group = create_colorize_bind_group(
+            &device,
+            &colorize_bind_group_layout,
+            &[
+                &settings_buffer,
+                &pixel_buffer,
+                &histogram_buffer,
+                &layers_buffer,
+                &accumulation_buffer,
+            ],
+            &[
+                (&palette_texture, &palette_sampler),
+                (&interior_palette_texture, &interior_palette_sampler),
+                (&layer_palette_texture, &layer_palette_sampler),
+            ],
+        );
+        let render_bundle = create_render_bundle(
+            &device,
+            &colorize_pipeline,
+            &colorize_bind_group,
+            swapchain_format,
+        );
 
         surface.configure(
             &device,
@@ -209,21 +719,95 @@ impl State {
             },
         );
 
-        Self {
+        let mut state = Self {
             device,
             queue,
             surface,
 
-            settings_buffer,
-            render_bundle,
+            orbit_buffer,
+            glitch_buffer,
+            bla_buffer,
+            fixed_point_buffer,
+            stats_buffer,
+            stats_readback_buffer,
+            glitch_readback_buffer,
+            layers_buffer,
+
+            iterate_pipelines: HashMap::new(),
+            iterate_pipeline_layout,
+            iterate_bind_group_layout,
+            stats_pipeline,
+            histogram_pipeline,
+            histogram_sum_pipeline,
+            edges_pipeline,
+
+            accumulate_pipeline,
+            colorize_pipeline,
+            colorize_bind_group_layout,
             swapchain_format,
+            palette_texture,
+            palette_sampler,
+            interior_palette_texture,
+            interior_palette_sampler,
+            layer_palette_texture,
+            layer_palette_sampler,
+
+            view: Viewport {
+                x: view_x,
+                y: view_y,
+                width: view_width,
+                height: view_height,
+                settings_buffer,
+                pixel_buffer,
+                histogram_buffer,
+                accumulation_buffer,
+                iterate_bind_group,
+                colorize_bind_group,
+                render_bundle,
+            },
+            julia_preview: None,
+            preview_c: Complex::default(),
 
             camera: Complex::default(),
-            zoom: INITIAL_ZOOM,
-        }
+            zoom: FloatExp::from(INITIAL_ZOOM as f64),
+            precision: Precision::Single,
+            reference: None,
+            secondary_references: Vec::new(),
+
+            formula: Formula::default(),
+            julia: false,
+            julia_c: None,
+            other_camera: Complex::default(),
+            other_zoom: FloatExp::from(INITIAL_ZOOM as f64),
+
+            iterations,
+            auto_iterations: true,
+            iteration_capacity: iterations,
+
+            coloring: Coloring::Discrete,
+            bailout: DEFAULT_BAILOUT,
+            palette,
+            lighting: None,
+            trap: OrbitTrap::default(),
+            stripe_frequency: DEFAULT_STRIPE_FREQUENCY,
+            interior_coloring: InteriorColoring::None,
+            interior_palette,
+            layers: Vec::new(),
+            palette_cycling: None,
+            dithering: true,
+            supersampling: None,
+            temporal_accumulation: true,
+            accumulated: None,
+            samples_buffer,
+            samples_capacity: 1,
+        };
+
+        state.update_camera();
+
+        state
     }
 
-    pub fn resize(&self, width: u32, height: u32) {
+    pub fn resize(&mut self, width: u32, height: u32) {
         // Reconfigure the surface for the new size
         self.surface.configure(
             &self.device,
@@ -236,15 +820,287 @@ impl State {
             },
         );
 
-        // Tell the GPU where the center of the screen now is
-        self.queue.write_buffer(
-            &self.settings_buffer,
-            0,
-            bytemuck::cast_slice(&[width as f32 / 2.0, height as f32 / 2.0]),
+        // Every pixel needs a slot in the pixel and accumulation buffers,
+        // so the viewports need to be recreated along with everything that refers to them.
+        let [x, y, width, height] = view_rect(
+            width,
+            height,
+            self.device.limits().max_storage_buffer_binding_size,
+        );
+        self.view.x = x;
+        self.view.y = y;
+        self.view.width = width;
+        self.view.height = height;
+        self.recreate_viewports();
+    }
+
+    /// Renders the image, or if nothing's changed since the last frame, adds another sample to it.
+    pub fn render(&mut self) {
+        let image = self.image();
+        let frames = match self.accumulated {
+            Some(ref accumulated)
+                if self.temporal_accumulation && accumulated.image.matches(&image) =>
+            {
+                accumulated.frames
+            }
+            _ => 0,
+        };
+
+        let passes = if frames == 0 {
+            Supersampling::passes(self.supersampling.as_ref())
+        } else if frames < MAX_ACCUMULATED_FRAMES {
+            vec![supersampling::temporal_pass(
+                frames - 1,
+                self.supersampling.as_ref(),
+            )]
+        } else {
+            // The image has been left to converge for long enough, so just put it back on the screen.
+            Vec::new()
+        };
+
+        let histogram = self
+            .colorings()
+            .any(|coloring| coloring == Coloring::Histogram);
+
+        let keep_samples = frames == 0
+            && passes.len() as u64 * pixel_buffer_size(self.view.width, self.view.height)
+                <= MAX_SAMPLES_SIZE;
+        if keep_samples && passes.len() as u32 > self.samples_capacity {
+            self.samples_capacity = passes.len() as u32;
+            self.samples_buffer = create_samples_buffer(
+                &self.device,
+                self.view.width,
+                self.view.height,
+                self.samples_capacity,
+            );
+        }
+
+        let mut glitched = false;
+        for (i, pass) in passes.iter().enumerate() {
+            self.iterate(pass);
+            if frames == 0 && i == 0 {
+                glitched = self.fix_glitches(pass);
+                if self.auto_iterations {
+                    self.count_iterations();
+                }
+                if pass.pixels == SamplePixels::Smooth {
+                    self.find_edges();
+                }
+            }
+            if keep_samples {
+                self.keep_sample(i as u32);
+            }
+            if histogram {
+                self.build_histogram(&self.view);
+            }
+            self.accumulate(&self.view);
+        }
+
+        self.render_preview();
+        self.colorize();
+
+        let passes = match self.accumulated.take() {
+            Some(accumulated) if frames > 0 => accumulated.passes,
+            _ if keep_samples => passes,
+            _ => Vec::new(),
+        };
+        self.accumulated = Some(Accumulated {
+            image,
+            frames: if glitched {
+                0
+            } else {
+                (frames + 1).min(MAX_ACCUMULATED_FRAMES)
+            },
+            passes,
+            preview_c: self.preview_c.clone(),
+        });
+    }
+
+    /// What the image rendered with the current settings would be.
+    fn image(&self) -> Image {
+        Image {
+            settings: self.settings(),
+            supersampling: self.supersampling,
+            camera: self.camera.clone(),
+            zoom: self.zoom,
+            julia_c: self.julia_c.clone().filter(|_| self.julia),
+            formula: self.formula,
+        }
+    }
+
+    /// Whether the pixels iterated by the last `render` were kept and are still the current image's, so that `recolor` can be used instead.
+    pub fn iterated(&self) -> bool {
+        let image = self.image();
+        self.accumulated.as_ref().is_some_and(|accumulated| {
+            accumulated.frames > 0
+                && !accumulated.passes.is_empty()
+                && accumulated.image.same_pixels(&image)
+                && (self.visible_preview().is_none() || accumulated.preview_c == self.preview_c)
+        })
+    }
+
+    /// Whether rendering another frame would change the image, because it's still converging
+    /// or there's a new secondary reference to render it with.
+    pub fn accumulating(&self) -> bool {
+        self.accumulated.as_ref().is_some_and(|accumulated| {
+            accumulated.frames == 0
+                || (self.temporal_accumulation && accumulated.frames < MAX_ACCUMULATED_FRAMES)
+        })
+    }
+
+    /// Throws away the samples accumulated so far, for changes `render` can't tell have happened from the `Image`.
+    fn reset_accumulation(&mut self) {
+        self.accumulated = None;
+    }
+
+    /// Redraws the image from the pixels that were already iterated by the last `render`, without iterating anything,
+    /// for when nothing but the colouring has changed since then (like the palette's offset when it's cycling; see `iterated`).
+    ///
+    /// Every sample of the first frame's passes gets coloured again, but any added since by temporal accumulation are dropped,
+    /// until the next `render` starts adding them back.
+    /// This does nothing if there aren't any pixels to colour, since they were thrown away after `render`.
+    pub fn recolor(&mut self) {
+        let settings = self.settings();
+        let accumulated = match self.accumulated {
+            Some(ref accumulated) => accumulated,
+            None => return,
+        };
+
+        let histogram = self
+            .colorings()
+            .any(|coloring| coloring == Coloring::Histogram);
+
+        for (i, pass) in accumulated.passes.iter().enumerate() {
+            self.restore_sample(i as u32);
+            self.queue.write_buffer(
+                &self.view.settings_buffer,
+                0,
+                bytemuck::bytes_of(&settings.for_pass(pass)),
+            );
+            if histogram {
+                self.build_histogram(&self.view);
+            }
+            self.accumulate(&self.view);
+        }
+
+        if let Some(preview) = self.visible_preview() {
+            let preview_settings = self.preview_settings(preview);
+            self.queue.write_buffer(
+                &preview.settings_buffer,
+                0,
+                bytemuck::bytes_of(&preview_settings),
+            );
+            if histogram {
+                self.build_histogram(preview);
+            }
+            self.accumulate(preview);
+        }
+
+        self.colorize();
+
+        if let Some(ref mut accumulated) = self.accumulated {
+            accumulated.image.settings = settings;
+        }
+    }
+
+    /// Copies the main view's pixel buffer into the `index`th slot of the samples buffer.
+    fn keep_sample(&self, index: u32) {
+        let size = pixel_buffer_size(self.view.width, self.view.height);
+        self.copy_buffer(
+            (&self.view.pixel_buffer, 0),
+            (&self.samples_buffer, index as u64 * size),
+            size,
         );
     }
 
-    pub fn render(&self) {
+    /// Copies the `index`th slot of the samples buffer back into the main view's pixel buffer.
+    fn restore_sample(&self, index: u32) {
+        let size = pixel_buffer_size(self.view.width, self.view.height);
+        self.copy_buffer(
+            (&self.samples_buffer, index as u64 * size),
+            (&self.view.pixel_buffer, 0),
+            size,
+        );
+    }
+
+    /// Copies `size` bytes from one buffer to another, each given along with the offset into it.
+    fn copy_buffer(
+        &self,
+        (source, source_offset): (&Buffer, u64),
+        (destination, destination_offset): (&Buffer, u64),
+        size: u64,
+    ) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Copy command encoder"),
+            });
+        encoder.copy_buffer_to_buffer(source, source_offset, destination, destination_offset, size);
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Moves the palette along by however far it cycles in `seconds`, if it's cycling.
+    pub fn cycle_palette(&mut self, seconds: f32) {
+        if let Some(cycling) = self.palette_cycling {
+            // Every wrap mode but `Clamp` repeats after 2, so keep the offset small without it jumping.
+            self.palette.offset =
+                (self.palette.offset + cycling.velocity() * seconds).rem_euclid(2.0);
+        }
+    }
+
+    /// Colours `viewport`'s pixel buffer into its accumulation buffer, according to the pass in its settings buffer.
+    fn accumulate(&self, viewport: &Viewport) {
+        self.compute_pass(
+            "Accumulate",
+            &self.accumulate_pipeline,
+            &viewport.colorize_bind_group,
+            viewport,
+        );
+    }
+
+    /// Marks the pixels on edges, for adaptive supersampling.
+    fn find_edges(&self) {
+        self.compute_pass(
+            "Edges",
+            &self.edges_pipeline,
+            &self.view.iterate_bind_group,
+            &self.view,
+        );
+    }
+
+    /// Runs `pipeline` over every pixel of `viewport`, with `bind_group` being one of its bind groups.
+    fn compute_pass(
+        &self,
+        label: &str,
+        pipeline: &ComputePipeline,
+        bind_group: &BindGroup,
+        viewport: &Viewport,
+    ) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some(&format!("{} command encoder", label)),
+            });
+
+        {
+            let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some(&format!("{} pass", label)),
+            });
+
+            cpass.set_pipeline(pipeline);
+            cpass.set_bind_group(0, bind_group, &[]);
+            cpass.dispatch(
+                viewport.width.div_ceil(WORKGROUP_SIZE),
+                viewport.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Averages each viewport's accumulation buffer onto its part of the screen.
+    fn colorize(&self) {
         let frame = self
             .surface
             .get_current_frame()
@@ -274,23 +1130,1209 @@ impl State {
                 depth_stencil_attachment: None,
             });
 
-            rpass.execute_bundles(iter::once(&self.render_bundle));
+            for viewport in self.viewports() {
+                rpass.set_viewport(
+                    viewport.x as f32,
+                    viewport.y as f32,
+                    viewport.width as f32,
+                    viewport.height as f32,
+                    0.0,
+                    1.0,
+                );
+                rpass.execute_bundles(iter::once(&viewport.render_bundle));
+            }
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Iterates `pass`'s sample of every pixel into the pixel buffer.
+    fn iterate(&self, pass: &SamplePass) {
+        if let Precision::FixedPoint { limbs } = self.precision {
+            let pixel_size = Component::from(self.pixel_size());
+            let julia_c = self.julia_parameter().cloned().unwrap_or_default();
+            let limbs: Vec<u32> = [
+                &self.camera.real,
+                &self.camera.imag,
+                &pixel_size,
+                &julia_c.real,
+                &julia_c.imag,
+            ]
+            .iter()
+            .flat_map(|component| component.to_limbs(limbs as usize))
+            .collect();
+            self.queue
+                .write_buffer(&self.fixed_point_buffer, 0, bytemuck::cast_slice(&limbs));
+        }
+
+        match self.reference {
+            Some(ref reference) => self.iterate_perturbation(reference, pass),
+            None => self.iterate_pass(&self.settings().for_pass(pass)),
+        }
+    }
+
+    /// Iterates every pixel relative to `reference`, using the secondary references picked so far to fix any glitches.
+    fn iterate_perturbation(&self, reference: &ReferenceOrbit, pass: &SamplePass) {
+        self.iterate_pass(
+            &self
+                .upload_reference(reference, [0.0, 0.0], false)
+                .for_pass(pass),
+        );
+
+        for (reference, offset) in &self.secondary_references {
+            self.iterate_pass(
+                &self
+                    .upload_reference(reference, *offset, true)
+                    .for_pass(pass),
+            );
+        }
+    }
+
+    /// Reads back the glitches `pass` was left with, and picks a new secondary reference from within the worst one,
+    /// which re-renders them straight away and gets used by every pass after this.
+    ///
+    /// Returns whether one was picked, in which case the image needs rendering again, since it might not have fixed every glitch.
+    /// Reading the glitches back waits for the GPU to catch up, so this should only be done once a frame.
+    fn fix_glitches(&mut self, pass: &SamplePass) -> bool {
+        if self.reference.is_none()
+            || self.secondary_references.len() as u32 >= MAX_SECONDARY_REFERENCES
+        {
+            return false;
+        }
+
+        let index = match self
+            .read_buffer::<Glitches>(&self.glitch_readback_buffer)
+            .and_then(|glitches| glitches.worst_pixel())
+        {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let offset = self.pixel_offset(index % self.view.width, index / self.view.width);
+
+        let mut c = self.camera.clone() + &self.to_complex_offset(offset);
+        c.set_precision(self.comp_size());
+        let reference = self.reference_orbit(c);
+        let offset = [offset[0] as f32, offset[1] as f32];
+
+        self.iterate_pass(
+            &self
+                .upload_reference(&reference, offset, true)
+                .for_pass(pass),
+        );
+        self.secondary_references.push((reference, offset));
+        true
+    }
+
+    /// The settings for iterating pixels without a reference orbit.
+    fn settings(&self) -> Settings {
+        let camera = [
+            DoubleSingle::from(&self.camera.real),
+            DoubleSingle::from(&self.camera.imag),
+        ];
+        let julia_c = self
+            .julia_parameter()
+            .map_or([DoubleSingle::default(); 2], |c| {
+                [DoubleSingle::from(&c.real), DoubleSingle::from(&c.imag)]
+            });
+        let (pixel_size, pixel_size_exponent) = self.pixel_size().to_f32_parts();
+        let lighting = self.lighting.unwrap_or_default();
+
+        Settings {
+            center: [self.view.width as f32 / 2.0, self.view.height as f32 / 2.0],
+            camera: [camera[0].hi, camera[1].hi],
+            camera_low: [camera[0].lo, camera[1].lo],
+            reference_offset: [0.0, 0.0],
+
+            series: [[0.0; 2]; 3],
+
+            width: self.view.width,
+            height: self.view.height,
+
+            iterations: self.iterations,
+            pixel_size,
+            pixel_size_exponent,
+
+            reference_len: 0,
+            secondary: 0,
+
+            series_skip: 0,
+            series_exponents: [0; 3],
+
+            bla_levels: 0,
+
+            near_limit: iterations::near_limit(self.iterations),
+            period_tolerance: self
+                .precision
+                .period_tolerance()
+                .map_or(0.0, |tolerance| tolerance.to_f64() as f32),
+
+            bailout: self.bailout,
+            coloring: self.coloring as u32,
+
+            palette_offset: self.palette.offset,
+            palette_scale: self.palette.scale,
+
+            lighting: self.lighting.is_some() as u32,
+            light_angle: lighting.angle,
+            light_elevation: lighting.elevation,
+            ambient: lighting.ambient,
+            specular: lighting.specular,
+            slope_depth: lighting.depth,
+
+            trap_center: self.trap.center,
+            trap_shape: self.trap.shape as u32,
+            trap_angle: self.trap.angle,
+            trap_radius: self.trap.radius,
+            stripe_frequency: self.stripe_frequency,
+
+            interior_coloring: self.interior_coloring as u32,
+            interior_palette_offset: self.interior_palette.offset,
+            interior_palette_scale: self.interior_palette.scale,
+
+            orbit_tracking: self.orbit_tracking(),
+
+            encode_srgb: !output::is_srgb(self.swapchain_format) as u32,
+            dither_step: if self.dithering {
+                output::quantization_step(self.swapchain_format)
+            } else {
+                0.0
+            },
+
+            sample_offset: [0.0, 0.0],
+            sample_pixels: SamplePixels::All as u32,
+            accumulate: false as u32,
+            edge_threshold: self
+                .supersampling
+                .map_or(DEFAULT_EDGE_THRESHOLD, |supersampling| {
+                    supersampling.threshold
+                }),
+
+            julia: self.julia_parameter().is_some() as u32,
+            julia_c: [julia_c[0].hi, julia_c[1].hi],
+            julia_c_low: [julia_c[0].lo, julia_c[1].lo],
+
+            origin: [self.view.x, self.view.y],
+
+            log_degree: self.formula.log_degree(),
+            padding: 0,
+        }
+    }
+
+    /// Uploads `reference` and its BLA table, and returns the settings for iterating pixels relative to it,
+    /// where it's `reference_offset` pixels away from the camera.
+    ///
+    /// If `secondary` is true, only pixels which were glitched in the previous pass are re-rendered.
+    fn upload_reference(
+        &self,
+        reference: &ReferenceOrbit,
+        reference_offset: [f32; 2],
+        secondary: bool,
+    ) -> Settings {
+        self.queue.write_buffer(
+            &self.orbit_buffer,
+            0,
+            bytemuck::cast_slice(&reference.orbit),
+        );
+
+        if self.orbit_tracking() != 0 {
+            // Orbit traps and stripes need to see every iteration, so neither series approximation nor BLA can skip any.
+            // The series still gives a Julia set's pixels their δz₀ without skipping anything, though.
+            let (series_mantissas, series_exponents) =
+                SeriesApproximation::new(reference, 0, 0.0, self.pixel_size()).gpu_coefficients();
+            return Settings {
+                reference_offset,
+
+                series: series_mantissas,
+                series_exponents,
+
+                reference_len: reference.orbit.len() as u32,
+                secondary: secondary as u32,
+                ..self.settings()
+            };
+        }
+
+        // The approximation needs to hold for every pixel on the screen, so use the distance to the furthest corner.
+        let radius = (self.view.width as f64).hypot(self.view.height as f64) / 2.0
+            + (reference_offset[0] as f64).hypot(reference_offset[1] as f64);
+        let series =
+            SeriesApproximation::new(reference, self.iterations, radius, self.pixel_size());
+        let (series_mantissas, series_exponents) = series.gpu_coefficients();
+
+        let bla = BlaTable::new(reference, FloatExp::from(radius) * self.pixel_size());
+        self.queue.write_buffer(
+            &self.bla_buffer,
+            0,
+            bytemuck::cast_slice(&bla.gpu_entries()),
+        );
+
+        Settings {
+            reference_offset,
+
+            series: series_mantissas,
+
+            reference_len: reference.orbit.len() as u32,
+            secondary: secondary as u32,
+
+            series_skip: series.skip,
+            series_exponents,
+
+            bla_levels: bla.gpu_levels(),
+            ..self.settings()
+        }
+    }
+
+    /// Runs the iteration shader for the current precision with `settings`.
+    fn iterate_pass(&self, settings: &Settings) {
+        self.queue
+            .write_buffer(&self.view.settings_buffer, 0, bytemuck::bytes_of(settings));
+        self.queue
+            .write_buffer(&self.glitch_buffer, 0, bytemuck::bytes_of(&Glitches::EMPTY));
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Iterate command encoder"),
+            });
+
+        {
+            let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Iterate pass"),
+            });
+
+            cpass.set_pipeline(&self.iterate_pipelines[&(self.precision, self.formula)]);
+            cpass.set_bind_group(0, &self.view.iterate_bind_group, &[]);
+            cpass.dispatch(
+                self.view.width.div_ceil(WORKGROUP_SIZE),
+                self.view.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
         }
 
+        encoder.copy_buffer_to_buffer(
+            &self.glitch_buffer,
+            0,
+            &self.glitch_readback_buffer,
+            0,
+            size_of::<Glitches>() as u64,
+        );
+
         self.queue.submit(Some(encoder.finish()));
     }
 
-    /// Update the camera's position and zoom in the GPU's buffer to the latest values.
-    pub fn update_camera(&self) {
+    /// Counts how many pixels hit the iteration limit or came close to it, for `update_iterations` to read back.
+    fn count_iterations(&self) {
+        self.queue.write_buffer(
+            &self.stats_buffer,
+            0,
+            bytemuck::bytes_of(&IterationStats::EMPTY),
+        );
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Iteration stats command encoder"),
+            });
+
+        {
+            let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Iteration stats pass"),
+            });
+
+            cpass.set_pipeline(&self.stats_pipeline);
+            cpass.set_bind_group(0, &self.view.iterate_bind_group, &[]);
+            cpass.dispatch(
+                self.view.width.div_ceil(WORKGROUP_SIZE),
+                self.view.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+
+        encoder.copy_buffer_to_buffer(
+            &self.stats_buffer,
+            0,
+            &self.stats_readback_buffer,
+            0,
+            size_of::<IterationStats>() as u64,
+        );
+
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Builds the cumulative histogram of `viewport`'s pixels' iteration counts for `colorize.wgsl` to map them through.
+    fn build_histogram(&self, viewport: &Viewport) {
+        self.queue.write_buffer(
+            &viewport.histogram_buffer,
+            0,
+            bytemuck::cast_slice(&[0u32; HISTOGRAM_BINS as usize]),
+        );
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Histogram command encoder"),
+            });
+
+        {
+            let mut cpass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Histogram pass"),
+            });
+
+            cpass.set_bind_group(0, &viewport.iterate_bind_group, &[]);
+            cpass.set_pipeline(&self.histogram_pipeline);
+            cpass.dispatch(
+                viewport.width.div_ceil(WORKGROUP_SIZE),
+                viewport.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+            cpass.set_pipeline(&self.histogram_sum_pipeline);
+            cpass.dispatch(1, 1, 1);
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+    }
+
+    /// Adjusts the iteration limit based on how many pixels hit it in the last frame, if it's being picked automatically.
+    ///
+    /// Returns whether it changed, in which case the frame should be redrawn.
+    pub fn update_iterations(&mut self) -> bool {
+        if !self.auto_iterations {
+            return false;
+        }
+
+        let minimum = iterations::for_zoom(self.zoom);
+        let next = match self.read_buffer::<IterationStats>(&self.stats_readback_buffer) {
+            Some(stats) => iterations::adjust(self.iterations, stats, minimum),
+            // We can't read back the stats on the web, so just go by the zoom.
+            None => minimum,
+        };
+
+        if next == self.iterations {
+            return false;
+        }
+
+        self.set_iteration_limit(next);
+        self.update_camera();
+        true
+    }
+
+    /// Sets the bailout radius, clamped to what the arithmetic can handle.
+    pub fn set_bailout(&mut self, bailout: f32) {
+        self.bailout = bailout.clamp(2.0, MAX_BAILOUT);
+        // The reference orbit stops at the bailout, so it needs to be recomputed.
+        self.update_camera();
+    }
+
+    /// Switches to a different palette, re-uploading it to the GPU.
+    pub fn set_palette(&mut self, palette: Palette) {
+        write_palette(&self.queue, &self.palette_texture, 0, &palette);
+        if palette.wrap != self.palette.wrap {
+            self.palette_sampler = create_palette_sampler(&self.device, palette.wrap);
+            self.recreate_viewports();
+        }
+        self.palette = palette;
+        self.reset_accumulation();
+    }
+
+    /// Switches to a different palette for pixels in the set, re-uploading it to the GPU.
+    pub fn set_interior_palette(&mut self, palette: Palette) {
+        write_palette(&self.queue, &self.interior_palette_texture, 0, &palette);
+        if palette.wrap != self.interior_palette.wrap {
+            self.interior_palette_sampler = create_palette_sampler(&self.device, palette.wrap);
+            self.recreate_viewports();
+        }
+        self.interior_palette = palette;
+        self.reset_accumulation();
+    }
+
+    /// Switches to a different stack of layers on top of the main colouring, re-uploading them to the GPU.
+    ///
+    /// Only the first `MAX_LAYERS` are used.
+    pub fn set_layers(&mut self, mut layers: Vec<Layer>) {
+        if layers.len() > layer::MAX_LAYERS {
+            log::warn!(
+                "only the first {} of {} layers are used",
+                layer::MAX_LAYERS,
+                layers.len()
+            );
+            layers.truncate(layer::MAX_LAYERS);
+        }
+
+        for (row, layer) in layers.iter().enumerate() {
+            write_palette(
+                &self.queue,
+                &self.layer_palette_texture,
+                row as u32,
+                &layer.palette,
+            );
+        }
+        self.queue.write_buffer(
+            &self.layers_buffer,
+            0,
+            bytemuck::bytes_of(&GpuLayers::new(&layers)),
+        );
+        self.layers = layers;
+        self.reset_accumulation();
+    }
+
+    /// The main colouring followed by every layer's.
+    fn colorings(&self) -> impl Iterator<Item = Coloring> + '_ {
+        iter::once(self.coloring).chain(self.layers.iter().map(|layer| layer.coloring))
+    }
+
+    /// The `TRACK_*` flags for everything the colourings need built up along each orbit.
+    fn orbit_tracking(&self) -> u32 {
+        self.colorings()
+            .fold(0, |flags, coloring| flags | coloring.orbit_tracking())
+    }
+
+    /// Sets the iteration limit manually, turning off `auto_iterations`.
+    pub fn set_iterations(&mut self, iterations: u32) {
+        self.auto_iterations = false;
+        self.set_iteration_limit(iterations);
+        self.update_camera();
+    }
+
+    /// Sets the iteration limit, making room for it in the buffers which depend on it.
+    ///
+    /// The reference orbit still needs to be recomputed afterwards.
+    fn set_iteration_limit(&mut self, iterations: u32) {
+        self.iterations = iterations;
+
+        if iterations > self.iteration_capacity {
+            self.orbit_buffer = create_orbit_buffer(&self.device, iterations);
+            self.bla_buffer = create_bla_buffer(&self.device, iterations);
+            self.iteration_capacity = iterations;
+            self.recreate_viewports();
+        }
+
         self.queue.write_buffer(
-            &self.settings_buffer,
-            8,
-            bytemuck::cast_slice(&[self.camera.real, self.camera.imag, self.zoom]),
+            &self.view.settings_buffer,
+            0,
+            bytemuck::bytes_of(&self.settings()),
+        );
+    }
+
+    /// Creates a viewport covering the part of the window `width` by `height` from (`x`, `y`),
+    /// with its own settings, pixel, histogram and accumulation buffers and the bind groups which use them.
+    fn create_viewport(&self, [x, y, width, height]: [u32; 4]) -> Viewport {
+        let settings_buffer = create_settings_buffer(&self.device);
+        let pixel_buffer = create_pixel_buffer(&self.device, width, height);
+        let histogram_buffer = create_histogram_buffer(&self.device);
+        let accumulation_buffer = create_accumulation_buffer(&self.device, width, height);
+
+        let iterate_bind_group = create_iterate_bind_group(
+            &self.device,
+            &self.iterate_bind_group_layout,
+            &[
+                &settings_buffer,
+                &pixel_buffer,
+                &self.orbit_buffer,
+                &self.glitch_buffer,
+                &self.fixed_point_buffer,
+                &self.bla_buffer,
+                &self.stats_buffer,
+                &histogram_buffer,
+            ],
+        );
+        let colorize_bind_group = create_colorize_bind_group(
+            &self.device,
+            &self.colorize_bind_group_layout,
+            &[
+                &settings_buffer,
+                &pixel_buffer,
+                &histogram_buffer,
+                &self.layers_buffer,
+                &accumulation_buffer,
+            ],
+            &[
+                (&self.palette_texture, &self.palette_sampler),
+                (
+                    &self.interior_palette_texture,
+                    &self.interior_palette_sampler,
+                ),
+                (&self.layer_palette_texture, &self.layer_palette_sampler),
+            ],
+        );
+        let render_bundle = create_render_bundle(
+            &self.device,
+            &self.colorize_pipeline,
+            &colorize_bind_group,
+            self.swapchain_format,
+        );
+
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+            settings_buffer,
+            pixel_buffer,
+            histogram_buffer,
+            accumulation_buffer,
+            iterate_bind_group,
+            colorize_bind_group,
+            render_bundle,
+        }
+    }
+
+    /// Recreates the viewports at their current sizes (and the preview to fit the main one),
+    /// for when the window's been resized or something their bind groups use has been replaced.
+    ///
+    /// This throws away their pixels, so the image has to be rendered again from scratch.
+    fn recreate_viewports(&mut self) {
+        self.view =
+            self.create_viewport([self.view.x, self.view.y, self.view.width, self.view.height]);
+        // The view might have grown, so start the samples buffer off small again and let `render` grow it to whatever still fits.
+        self.samples_capacity = 1;
+        self.samples_buffer = create_samples_buffer(
+            &self.device,
+            self.view.width,
+            self.view.height,
+            self.samples_capacity,
+        );
+        if self.julia_preview.is_some() {
+            self.julia_preview = Some(self.create_viewport(self.view.preview_rect()));
+        }
+        self.reset_accumulation();
+    }
+
+    /// The viewports which get drawn this frame, in the order they're drawn.
+    fn viewports(&self) -> impl Iterator<Item = &Viewport> {
+        iter::once(&self.view).chain(self.visible_preview())
+    }
+
+    /// Blocks until the GPU is done with `buffer`, and then reads a `T` from the start of it.
+    ///
+    /// Returns `None` on the web, where we can't block; for glitches, that means they're left flagged in `GLITCH_COLOR`.
+    fn read_buffer<T: Pod>(&self, buffer: &Buffer) -> Option<T> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let slice = buffer.slice(..);
+            let mapping = slice.map_async(wgpu::MapMode::Read);
+            self.device.poll(wgpu::Maintain::Wait);
+            pollster::block_on(mapping).ok()?;
+
+            let value = *bytemuck::from_bytes(&slice.get_mapped_range()[..size_of::<T>()]);
+            buffer.unmap();
+            Some(value)
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            let _ = buffer;
+            None
+        }
+    }
+
+    /// The distance between adjacent pixels on the complex plane.
+    pub fn pixel_size(&self) -> FloatExp {
+        FloatExp::from(1.0) / self.zoom
+    }
+
+    /// Gets the offset in pixels of the center of the pixel at (`x`, `y`) from the center of the screen, oriented like the complex plane.
+    pub fn pixel_offset(&self, x: u32, y: u32) -> [f64; 2] {
+        let x_offset = x as f64 + 0.5 - self.view.width as f64 / 2.0;
+        // Flip around the y, since in pixel space y gets bigger going downwards, whereas on the complex plane it's the reverse.
+        let y_offset = -(y as f64 + 0.5 - self.view.height as f64 / 2.0);
+        [x_offset, y_offset]
+    }
+
+    /// Converts an offset in physical pixels to an offset on the complex plane at the current zoom.
+    pub fn to_complex_offset(&self, [x, y]: [f64; 2]) -> Complex {
+        let pixel_size = self.pixel_size();
+        Complex::new(
+            (FloatExp::from(x) * pixel_size).into(),
+            (FloatExp::from(y) * pixel_size).into(),
         )
     }
 
-    // Gets the target length of components' subints given the current level of zoom.
-    pub fn comp_size(&self) {
-        
+    /// Pick the precision to iterate with and recompute the reference orbit after the camera's position or zoom has changed.
+    pub fn update_camera(&mut self) {
+        // The camera doesn't need any more precision than the reference orbit does.
+        self.camera.set_precision(self.comp_size());
+
+        // Don't wait for a frame's stats to catch up after zooming in past what the limit can handle.
+        let minimum = iterations::for_zoom(self.zoom);
+        if self.auto_iterations && self.iterations < minimum {
+            self.set_iteration_limit(minimum);
+        }
+
+        self.precision = match Precision::choose(self.pixel_size(), self.radius()) {
+            // Fixed point and perturbation only know how to iterate z² + c, so anything else is stuck with double-single,
+            // which is why `max_zoom` stops it going any deeper than that can resolve.
+            Precision::FixedPoint { .. } | Precision::Perturbation
+                if !self.formula.is_mandelbrot() =>
+            {
+                Precision::DoubleSingle
+            }
+            precision => precision,
+        };
+        self.iterate_pipeline(self.precision);
+
+        self.secondary_references.clear();
+        self.reference = match self.precision {
+            Precision::Perturbation => Some(self.reference_orbit(self.camera.clone())),
+            _ => None,
+        };
+    }
+
+    /// Creates the iteration pipeline for `precision` and the current formula, if it hasn't been already.
+    fn iterate_pipeline(&mut self, precision: Precision) {
+        let key = (precision, self.formula);
+        if !self.iterate_pipelines.contains_key(&key) {
+            let pipeline = create_iterate_pipeline(
+                &self.device,
+                &self.iterate_pipeline_layout,
+                precision,
+                self.formula,
+            );
+            self.iterate_pipelines.insert(key, pipeline);
+        }
+    }
+
+    /// Switches to iterating with `formula`, keeping the camera where it is,
+    /// but zooming out if it's too deep for `formula` (see `max_zoom`).
+    pub fn set_formula(&mut self, formula: Formula) {
+        self.formula = formula;
+        if let Some(max_zoom) = self.max_zoom() {
+            if self.zoom > max_zoom {
+                self.zoom = max_zoom;
+            }
+        }
+        if self.julia_preview.is_some() {
+            self.iterate_pipeline(Precision::Single);
+        }
+        self.reset_accumulation();
+        self.update_camera();
+    }
+
+    /// Iterates the reference orbit of `point` on the screen, which is c in the Mandelbrot set or z₀ in a Julia set.
+    fn reference_orbit(&self, point: Complex) -> ReferenceOrbit {
+        match self.julia_parameter() {
+            Some(c) => ReferenceOrbit::julia(point, c.clone(), self.iterations, self.bailout),
+            None => ReferenceOrbit::new(point, self.iterations, self.bailout),
+        }
+    }
+
+    /// The c of the Julia set being shown, or `None` if it's the Mandelbrot set.
+    pub fn julia_parameter(&self) -> Option<&Complex> {
+        self.julia_c.as_ref().filter(|_| self.julia)
+    }
+
+    /// Shows the Julia set of `c`, from its default view.
+    pub fn pick_julia(&mut self, c: Complex) {
+        self.julia_c = Some(c);
+        if self.julia {
+            self.camera = Complex::default();
+            self.zoom = FloatExp::from(INITIAL_ZOOM as f64);
+            self.update_camera();
+        } else {
+            self.other_camera = Complex::default();
+            self.other_zoom = FloatExp::from(INITIAL_ZOOM as f64);
+            self.toggle_julia();
+        }
+    }
+
+    /// Turns the Julia set preview in the corner of the window on or off.
+    pub fn set_julia_preview(&mut self, enabled: bool) {
+        self.julia_preview = if enabled {
+            // The preview's always shallow enough for single precision, whatever the main view's using.
+            self.iterate_pipeline(Precision::Single);
+            Some(self.create_viewport(self.view.preview_rect()))
+        } else {
+            None
+        };
+    }
+
+    /// The Julia set preview, if it's turned on and the Mandelbrot set is being shown rather than a Julia set.
+    fn visible_preview(&self) -> Option<&Viewport> {
+        self.julia_preview.as_ref().filter(|_| !self.julia)
+    }
+
+    /// Iterates and colours the Julia set preview, if it's visible.
+    ///
+    /// It gets rendered from scratch every frame, since the cursor could have moved since the last one.
+    fn render_preview(&self) {
+        let preview = match self.visible_preview() {
+            Some(preview) => preview,
+            None => return,
+        };
+
+        let settings = self.preview_settings(preview);
+        self.queue
+            .write_buffer(&preview.settings_buffer, 0, bytemuck::bytes_of(&settings));
+        self.compute_pass(
+            "Preview iterate",
+            &self.iterate_pipelines[&(Precision::Single, self.formula)],
+            &preview.iterate_bind_group,
+            preview,
+        );
+        if self
+            .colorings()
+            .any(|coloring| coloring == Coloring::Histogram)
+        {
+            self.build_histogram(preview);
+        }
+        self.accumulate(preview);
+    }
+
+    /// The settings for rendering the Julia set of `preview_c` into `preview`, from the same view a picked Julia set starts off with.
+    fn preview_settings(&self, preview: &Viewport) -> Settings {
+        let zoom = FloatExp::from((INITIAL_ZOOM * PREVIEW_SCALE) as f64);
+        let (pixel_size, pixel_size_exponent) = (FloatExp::from(1.0) / zoom).to_f32_parts();
+        let iterations = self.iterations.min(MAX_PREVIEW_ITERATIONS);
+        let c = [
+            DoubleSingle::from(&self.preview_c.real),
+            DoubleSingle::from(&self.preview_c.imag),
+        ];
+
+        Settings {
+            center: [preview.width as f32 / 2.0, preview.height as f32 / 2.0],
+            camera: [0.0, 0.0],
+            camera_low: [0.0, 0.0],
+
+            width: preview.width,
+            height: preview.height,
+
+            iterations,
+            pixel_size,
+            pixel_size_exponent,
+
+            near_limit: iterations::near_limit(iterations),
+            period_tolerance: Precision::Single
+                .period_tolerance()
+                .map_or(0.0, |tolerance| tolerance.to_f64() as f32),
+
+            julia: true as u32,
+            julia_c: [c[0].hi, c[1].hi],
+            julia_c_low: [c[0].lo, c[1].lo],
+
+            origin: [preview.x, preview.y],
+            ..self.settings()
+        }
+    }
+
+    /// Switches between the Mandelbrot set and the last Julia set picked, each with the camera it was left with.
+    /// This does nothing if no Julia set has been picked yet.
+    pub fn toggle_julia(&mut self) {
+        if self.julia_c.is_none() {
+            return;
+        }
+        mem::swap(&mut self.camera, &mut self.other_camera);
+        mem::swap(&mut self.zoom, &mut self.other_zoom);
+        self.julia = !self.julia;
+        self.update_camera();
+    }
+
+    /// The furthest any point on the screen is from the origin.
+    pub fn radius(&self) -> FloatExp {
+        let real = FloatExp::from(&self.camera.real);
+        let imag = FloatExp::from(&self.camera.imag);
+        let half_diagonal = (self.view.width as f64).hypot(self.view.height as f64) / 2.0;
+        (real * real + imag * imag).sqrt() + FloatExp::from(half_diagonal) * self.pixel_size()
+    }
+
+    /// Whether adjacent pixels are closer together than the current precision can tell apart, making the image pixelated.
+    ///
+    /// The precision is picked to avoid this, so this should only ever happen if none of the options are precise enough,
+    /// or only double-single can be used with the formula and it's been zoomed in past `max_zoom` anyway.
+    pub fn pixelated(&self) -> bool {
+        !self
+            .precision
+            .resolves(precision::spacing(self.pixel_size()), self.radius())
+    }
+
+    /// The furthest the view can be zoomed in before it gets pixelated, if there's a limit.
+    ///
+    /// Only z² + c can be iterated with fixed point and perturbation, so any other formula stops where double-single does.
+    pub fn max_zoom(&self) -> Option<FloatExp> {
+        if self.formula.is_mandelbrot() {
+            return None;
+        }
+        Some(FloatExp::from(1.0) / Precision::DoubleSingle.min_pixel_size(self.radius()))
+    }
+
+    /// Gets the target length of components' subints given the current level of zoom.
+    pub fn comp_size(&self) -> usize {
+        // We need enough bits to tell apart adjacent pixels,
+        // plus enough extra that rounding the reference orbit is invisible next to the `f32` deltas the GPU uses.
+        let bits = self.zoom.log2() + f32::MANTISSA_DIGITS as f64;
+        cmp::max((bits / 32.0).ceil() as usize, 1)
+    }
+}
+
+fn settings_layout_entry(visibility: ShaderStages) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding: 0,
+        visibility,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(size_of::<Settings>() as u64),
+        },
+        count: None,
+    }
+}
+
+fn storage_layout_entry(
+    binding: u32,
+    visibility: ShaderStages,
+    read_only: bool,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: BindingType::Buffer {
+            ty: BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+fn buffer_entry(binding: u32, buffer: &Buffer) -> BindGroupEntry<'_> {
+    BindGroupEntry {
+        binding,
+        resource: BindingResource::Buffer(BufferBinding {
+            buffer,
+            offset: 0,
+            size: None,
+        }),
+    }
+}
+
+fn create_settings_buffer(device: &Device) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Settings buffer"),
+        size: size_of::<Settings>() as u64,
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_pixel_buffer(device: &Device, width: u32, height: u32) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Pixel buffer"),
+        size: pixel_buffer_size(width, height),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// The part of a window `width` by `height` the main view covers, as `[x, y, width, height]`.
+///
+/// That's the whole window, unless it has more pixels than fit in a pixel buffer of `max_binding_size` bytes,
+/// in which case the view is shrunk to fit and centred in the window, so that its center is still the window's.
+fn view_rect(width: u32, height: u32, max_binding_size: u32) -> [u32; 4] {
+    let max_pixels = max_binding_size as u64 / size_of::<Pixel>() as u64;
+    let pixels = width as u64 * height as u64;
+    if pixels <= max_pixels {
+        return [0, 0, width, height];
+    }
+
+    let scale = (max_pixels as f64 / pixels as f64).sqrt();
+    // Keep the margins on either side the same, so the view's center lands exactly on the window's.
+    let mut view_width = ((width as f64 * scale) as u32).max(1);
+    view_width -= (width - view_width) % 2;
+    let mut view_height = ((height as f64 * scale) as u32).max(1);
+    view_height -= (height - view_height) % 2;
+    while view_width as u64 * view_height as u64 > max_pixels {
+        view_width -= 2;
+        view_height -= 2;
     }
+
+    log::error!(
+        "the window is {}x{}, but the GPU can only hold {} pixels, so only the middle {}x{} is rendered",
+        width,
+        height,
+        max_pixels,
+        view_width,
+        view_height
+    );
+    [
+        (width - view_width) / 2,
+        (height - view_height) / 2,
+        view_width,
+        view_height,
+    ]
+}
+
+/// The size in bytes of a pixel buffer for a viewport `width` by `height`.
+fn pixel_buffer_size(width: u32, height: u32) -> u64 {
+    // Buffers can't be empty, so make sure there's room for at least one pixel even if the window's minimised.
+    cmp::max(width as u64 * height as u64, 1) * size_of::<Pixel>() as u64
+}
+
+fn create_samples_buffer(device: &Device, width: u32, height: u32, samples: u32) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Samples buffer"),
+        size: samples as u64 * pixel_buffer_size(width, height),
+        usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_accumulation_buffer(device: &Device, width: u32, height: u32) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Accumulation buffer"),
+        // One RGBA sum of f32s per pixel.
+        size: cmp::max(width as u64 * height as u64, 1) * 4 * size_of::<f32>() as u64,
+        usage: BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_histogram_buffer(device: &Device) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Histogram buffer"),
+        size: HISTOGRAM_BINS as u64 * size_of::<u32>() as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_orbit_buffer(device: &Device, iterations: u32) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("Reference orbit buffer"),
+        size: (iterations as u64 + 1) * size_of::<[f32; 2]>() as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_bla_buffer(device: &Device, iterations: u32) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("BLA buffer"),
+        // Each level has at most half as many steps as the one before, and the first level has less than one for every iteration.
+        size: 2 * iterations as u64 * size_of::<GpuBla>() as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Creates the pipeline which iterates pixels with `precision` and `formula`,
+/// which is ignored for fixed point and perturbation since they only support z² + c.
+fn create_iterate_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    precision: Precision,
+    formula: Formula,
+) -> ComputePipeline {
+    let (shader, entry_point) = match precision {
+        Precision::Single | Precision::DoubleSingle => {
+            let shader = ShaderModuleDescriptor {
+                label: Some("direct.wgsl"),
+                source: ShaderSource::Wgsl(Cow::Owned(direct_shader_source(formula))),
+            };
+            let entry_point = match precision {
+                Precision::Single => "single_main",
+                _ => "double_single_main",
+            };
+            (shader, entry_point)
+        }
+        Precision::FixedPoint { limbs } => {
+            // The number of limbs has to be known at compile time, so that it can be used as the length of arrays.
+            // The shader's count includes the integer limb.
+            let source = concat!(include_str!("common.wgsl"), include_str!("fixedpoint.wgsl"))
+                .replace("{limbs}", &(limbs + 1).to_string());
+            let shader = ShaderModuleDescriptor {
+                label: Some("fixedpoint.wgsl"),
+                source: ShaderSource::Wgsl(Cow::Owned(source)),
+            };
+            (shader, "main")
+        }
+        Precision::Perturbation => (
+            include_shader!("perturbation.wgsl", "floatexp.wgsl"),
+            "main",
+        ),
+    };
+
+    device.create_compute_pipeline(&ComputePipelineDescriptor {
+        label: Some("Iterate pipeline"),
+        layout: Some(layout),
+        module: &device.create_shader_module(&shader),
+        entry_point,
+    })
+}
+
+/// The source of `direct.wgsl` and everything it depends on, including the code generated for `formula`.
+fn direct_shader_source(formula: Formula) -> String {
+    let mut source = concat!(
+        include_str!("common.wgsl"),
+        include_str!("floatexp.wgsl"),
+        include_str!("doublesingle.wgsl"),
+    )
+    .to_string();
+    source += &formula.wgsl();
+    source += include_str!("direct.wgsl");
+    source
+}
+
+/// Creates the iteration shader's bind group, with each of `buffers` bound to its index.
+fn create_iterate_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    buffers: &[&Buffer],
+) -> BindGroup {
+    let entries: Vec<_> = buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| buffer_entry(binding as u32, buffer))
+        .collect();
+
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Iterate bind group"),
+        layout,
+        entries: &entries,
+    })
+}
+
+fn palette_texture_layout_entry(
+    binding: u32,
+    view_dimension: TextureViewDimension,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: COLORIZE_STAGES,
+        ty: BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable: true },
+            view_dimension,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+fn palette_sampler_layout_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: COLORIZE_STAGES,
+        ty: BindingType::Sampler {
+            filtering: true,
+            comparison: false,
+        },
+        count: None,
+    }
+}
+
+/// Creates a texture for `rows` palettes to be baked into with `write_palette`, which is 1D if there's only one.
+fn create_palette_texture(device: &Device, label: &str, rows: u32) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: Some(label),
+        size: Extent3d {
+            width: palette::TEXTURE_SIZE,
+            height: rows,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: if rows == 1 {
+            TextureDimension::D1
+        } else {
+            TextureDimension::D2
+        },
+        // The palette is baked in sRGB so that 8 bits per channel is enough, and gets converted back to linear when it's sampled.
+        format: TextureFormat::Rgba8UnormSrgb,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+    })
+}
+
+/// Bakes `palette` into row `row` of `texture`.
+fn write_palette(queue: &Queue, texture: &Texture, row: u32, palette: &Palette) {
+    queue.write_texture(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d { x: 0, y: row, z: 0 },
+            aspect: TextureAspect::All,
+        },
+        bytemuck::cast_slice(&palette.texels()),
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new(4 * palette::TEXTURE_SIZE),
+            rows_per_image: None,
+        },
+        Extent3d {
+            width: palette::TEXTURE_SIZE,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+/// Creates a sampler which blends between the palette's texels, and handles positions outside of 0 to 1 according to `wrap`.
+fn create_palette_sampler(device: &Device, wrap: Wrap) -> Sampler {
+    let address_mode = match wrap {
+        Wrap::Clamp => AddressMode::ClampToEdge,
+        Wrap::Repeat => AddressMode::Repeat,
+        Wrap::Mirror => AddressMode::MirrorRepeat,
+    };
+
+    device.create_sampler(&SamplerDescriptor {
+        label: Some("Palette sampler"),
+        address_mode_u: address_mode,
+        mag_filter: FilterMode::Linear,
+        min_filter: FilterMode::Linear,
+        ..Default::default()
+    })
+}
+
+/// Creates the bind group for `colorize.wgsl`, with each of `buffers` bound to its index,
+/// followed by each of `palettes`' textures and samplers.
+fn create_colorize_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    buffers: &[&Buffer],
+    palettes: &[(&Texture, &Sampler)],
+) -> BindGroup {
+    let palette_views: Vec<_> = palettes
+        .iter()
+        .map(|(texture, _)| texture.create_view(&TextureViewDescriptor::default()))
+        .collect();
+
+    let mut entries: Vec<_> = buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| buffer_entry(binding as u32, buffer))
+        .collect();
+    for (view, (_, sampler)) in palette_views.iter().zip(palettes) {
+        entries.push(BindGroupEntry {
+            binding: entries.len() as u32,
+            resource: BindingResource::TextureView(view),
+        });
+        entries.push(BindGroupEntry {
+            binding: entries.len() as u32,
+            resource: BindingResource::Sampler(sampler),
+        });
+    }
+
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("Colorize bind group"),
+        layout,
+        entries: &entries,
+    })
+}
+
+fn create_render_bundle(
+    device: &Device,
+    pipeline: &RenderPipeline,
+    bind_group: &BindGroup,
+    swapchain_format: TextureFormat,
+) -> RenderBundle {
+    let mut render_bundle_encoder =
+        device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+            label: Some("Render bundle encoder"),
+            color_formats: &[swapchain_format],
+            depth_stencil: None,
+            sample_count: 1,
+        });
+
+    render_bundle_encoder.set_pipeline(pipeline);
+    render_bundle_encoder.set_bind_group(0, bind_group, &[]);
+    render_bundle_encoder.draw(0..4, 0..1);
+
+    render_bundle_encoder.finish(&RenderBundleDescriptor {
+        label: Some("Render bundle"),
+    })
 }
@@ -1,16 +1,37 @@
+use gpu_mandelbrot::coloring::Coloring;
+use gpu_mandelbrot::coloring::Lighting;
+use gpu_mandelbrot::formula::Formula;
+use gpu_mandelbrot::interior::InteriorColoring;
+use gpu_mandelbrot::layer::Blend;
+use gpu_mandelbrot::layer::Layer;
+use gpu_mandelbrot::num::Complex;
+use gpu_mandelbrot::num::FloatExp;
+use gpu_mandelbrot::orbit::TrapShape;
+use gpu_mandelbrot::palette::Cycling;
+use gpu_mandelbrot::palette::Direction;
+use gpu_mandelbrot::palette::Palette;
+use gpu_mandelbrot::palette::Stop;
+use gpu_mandelbrot::supersampling::Pattern;
+use gpu_mandelbrot::supersampling::Supersampling;
 use gpu_mandelbrot::State;
 use gpu_mandelbrot::INITIAL_ZOOM;
-use winit::dpi::LogicalPosition;
-use winit::dpi::LogicalSize;
+use instant::Instant;
+use std::time::Duration;
 use winit::event::ElementState;
 use winit::event::Event;
+use winit::event::KeyboardInput;
 use winit::event::MouseButton;
 use winit::event::MouseScrollDelta;
+use winit::event::StartCause;
+use winit::event::VirtualKeyCode;
 use winit::event::WindowEvent;
 use winit::event_loop::ControlFlow;
 use winit::event_loop::EventLoop;
 use winit::window::Window;
 
+/// How long the palette waits between moving along while it's cycling, which is one frame at 60 FPS.
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
 fn main() {
     let event_loop = EventLoop::new();
     let window = Window::new(&event_loop).unwrap();
@@ -45,42 +66,72 @@ fn main() {
 async fn run(event_loop: EventLoop<()>, window: Window) {
     let mut state = State::new(&window).await;
 
-    // The mouse's offset in logical pixels from the center of the window.
+    // A palette file can be passed as the first argument, and one for the inside of the set as the second.
+    if let Some(path) = std::env::args().nth(1) {
+        match Palette::load(&path) {
+            Ok(palette) => state.set_palette(palette),
+            Err(error) => log::error!("failed to load palette from {}: {}", path, error),
+        }
+    }
+    if let Some(path) = std::env::args().nth(2) {
+        match Palette::load(&path) {
+            Ok(palette) => state.set_interior_palette(palette),
+            Err(error) => log::error!("failed to load interior palette from {}: {}", path, error),
+        }
+    }
+
+    // The mouse's offset in physical pixels from the center of the window, which are what the image is iterated in.
     let mut mouse_offset = [0.0, 0.0];
     let mut dragging = false;
+    // Whether the window is currently showing that the image is pixelated.
+    let mut pixelated = false;
+    // When the palette last moved along, while it's cycling.
+    let mut last_cycle = Instant::now();
+    // When the next frame is due while it's cycling.
+    let mut next_frame = Instant::now();
 
     event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Wait;
+        // Wake up for the next frame while the palette's cycling, so that it can keep moving.
+        *control_flow = if state.palette_cycling.is_some() {
+            ControlFlow::WaitUntil(next_frame)
+        } else {
+            ControlFlow::Wait
+        };
         match event {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                 WindowEvent::Resized(size) => {
                     state.resize(size.width, size.height);
+                    // The screen size affects the precision that's needed.
+                    state.update_camera();
+                    update_pixelated(&window, &state, &mut pixelated);
                     // At least on macOS, it doesn't seem like resizing triggers redraws on its own.
                     window.request_redraw();
                 }
                 WindowEvent::CursorMoved { position, .. } => {
-                    let scale_factor = window.scale_factor();
-                    let position: LogicalPosition<f32> = position.to_logical(scale_factor);
-                    let size: LogicalSize<f32> = window.inner_size().to_logical(scale_factor);
+                    let size = window.inner_size();
 
-                    let x_offset = position.x - size.width / 2.0;
-                    let y_offset = -(position.y - size.height / 2.0);
+                    let x_offset = position.x - size.width as f64 / 2.0;
+                    let y_offset = -(position.y - size.height as f64 / 2.0);
 
                     if dragging {
                         let x_delta = x_offset - mouse_offset[0];
                         let y_delta = y_offset - mouse_offset[1];
-                        state.camera = [
-                            state.camera[0] - x_delta / state.zoom,
-                            state.camera[1] - y_delta / state.zoom,
-                        ];
+                        state.camera -= &state.to_complex_offset([x_delta, y_delta]);
 
                         state.update_camera();
+                        update_pixelated(&window, &state, &mut pixelated);
 
                         window.request_redraw();
                     }
 
                     mouse_offset = [x_offset, y_offset];
+
+                    // Keep the Julia set preview following the cursor.
+                    if state.julia_preview.is_some() && !state.julia {
+                        state.preview_c = point_under(&state, mouse_offset);
+                        window.request_redraw();
+                    }
                 }
                 WindowEvent::MouseWheel { delta, .. } => {
                     let scrolled = match delta {
@@ -89,38 +140,373 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     };
 
                     // The old offset of the mouse from the camera in the complex plane.
-                    let old_offset = [mouse_offset[0] / state.zoom, mouse_offset[1] / state.zoom];
+                    let old_offset = state.to_complex_offset(mouse_offset);
 
-                    state.zoom *= 1.1f32.powf(scrolled);
-                    // Clamp the zoom to avoid having to deal with overflows in our fixed point numbers.
-                    state.zoom = f32::max(state.zoom, INITIAL_ZOOM);
+                    state.zoom = state.zoom * FloatExp::from(1.1f64.powf(scrolled as f64));
+                    // Clamp the zoom to avoid having to deal with overflows in our fixed point numbers,
+                    // and to stop formulas without deep zoom support before they get pixelated.
+                    if state.zoom < FloatExp::from(INITIAL_ZOOM as f64) {
+                        state.zoom = FloatExp::from(INITIAL_ZOOM as f64);
+                    }
+                    if let Some(max_zoom) = state.max_zoom() {
+                        if state.zoom > max_zoom {
+                            state.zoom = max_zoom;
+                        }
+                    }
 
                     // The new offset of the mouse from the camera in the complex plane.
-                    let new_offset = [mouse_offset[0] / state.zoom, mouse_offset[1] / state.zoom];
-
-                    let delta = [new_offset[0] - old_offset[0], new_offset[1] - old_offset[1]];
+                    let new_offset = state.to_complex_offset(mouse_offset);
 
                     // Cancel out the change in the mouse's position on the complex plane.
                     // This means that as you zoom in, the mouse will stay in the same spot.
-                    state.camera = [state.camera[0] - delta[0], state.camera[1] - delta[1]];
+                    state.camera -= &(new_offset - &old_offset);
 
                     state.update_camera();
+                    update_pixelated(&window, &state, &mut pixelated);
 
                     window.request_redraw();
                 }
-                WindowEvent::MouseInput { button, state, .. } => match (button, state) {
+                // Cycle through the colouring modes.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::S),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.coloring = match state.coloring {
+                        Coloring::Discrete => Coloring::Smooth,
+                        Coloring::Smooth => Coloring::Histogram,
+                        Coloring::Histogram => Coloring::Distance,
+                        Coloring::Distance => Coloring::Trap,
+                        Coloring::Trap => Coloring::Stripes,
+                        Coloring::Stripes => Coloring::Binary,
+                        Coloring::Binary => Coloring::FieldLines,
+                        Coloring::FieldLines => Coloring::Atom,
+                        Coloring::Atom => Coloring::Misiurewicz,
+                        Coloring::Misiurewicz => Coloring::Discrete,
+                    };
+                    window.request_redraw();
+                }
+                // Cycle through the ways of colouring the inside of the set.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::I),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.interior_coloring = match state.interior_coloring {
+                        InteriorColoring::None => InteriorColoring::Magnitude,
+                        InteriorColoring::Magnitude => InteriorColoring::Period,
+                        InteriorColoring::Period => InteriorColoring::Distance,
+                        InteriorColoring::Distance => InteriorColoring::Multiplier,
+                        InteriorColoring::Multiplier => InteriorColoring::None,
+                    };
+                    window.request_redraw();
+                }
+                // Toggle an example stack of layers: stripes overlaid on the main colouring, and then a white layer lit like a height field multiplied over the top.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::K),
+                            ..
+                        },
+                    ..
+                } => {
+                    if state.layers.is_empty() {
+                        let stripes = Layer {
+                            opacity: 0.5,
+                            blend: Blend::Overlay,
+                            ..Layer::new(Coloring::Stripes, Palette::default())
+                        };
+                        let white = Palette::new(vec![
+                            Stop {
+                                position: 0.0,
+                                color: [1.0; 3],
+                            },
+                            Stop {
+                                position: 1.0,
+                                color: [1.0; 3],
+                            },
+                        ]);
+                        let lighting = Layer {
+                            blend: Blend::Multiply,
+                            lighting: Some(Lighting::default()),
+                            ..Layer::new(Coloring::Smooth, white)
+                        };
+                        state.set_layers(vec![stripes, lighting]);
+                    } else {
+                        state.set_layers(Vec::new());
+                    }
+                    window.request_redraw();
+                }
+                // Toggle palette cycling.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::C),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.palette_cycling = match state.palette_cycling {
+                        Some(_) => None,
+                        None => {
+                            last_cycle = Instant::now();
+                            Some(Cycling::default())
+                        }
+                    };
+                }
+                // Reverse the direction the palette cycles in.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::R),
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Some(cycling) = &mut state.palette_cycling {
+                        cycling.direction = match cycling.direction {
+                            Direction::Forward => Direction::Backward,
+                            Direction::Backward => Direction::Forward,
+                        };
+                    }
+                }
+                // Cycle through the orbit trap's shapes.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::T),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.trap.shape = match state.trap.shape {
+                        TrapShape::Point => TrapShape::Line,
+                        TrapShape::Line => TrapShape::Cross,
+                        TrapShape::Cross => TrapShape::Point,
+                    };
+                    window.request_redraw();
+                }
+                // Toggle lighting the image like a height field.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::L),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.lighting = match state.lighting {
+                        Some(_) => None,
+                        None => Some(Lighting::default()),
+                    };
+                    window.request_redraw();
+                }
+                // Cycle through the supersampling patterns.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::A),
+                            ..
+                        },
+                    ..
+                } => {
+                    let pattern = match state
+                        .supersampling
+                        .map(|supersampling| supersampling.pattern)
+                    {
+                        None => Some(Pattern::Grid(2)),
+                        Some(Pattern::Grid(2)) => Some(Pattern::Grid(3)),
+                        Some(Pattern::Grid(3)) => Some(Pattern::Grid(4)),
+                        Some(Pattern::Grid(_)) => Some(Pattern::Jittered(4)),
+                        Some(Pattern::Jittered(_)) => Some(Pattern::RotatedGrid),
+                        Some(Pattern::RotatedGrid) => None,
+                    };
+                    let adaptive = state
+                        .supersampling
+                        .is_some_and(|supersampling| supersampling.adaptive);
+                    state.supersampling = pattern.map(|pattern| Supersampling {
+                        adaptive,
+                        ..Supersampling::new(pattern)
+                    });
+                    window.request_redraw();
+                }
+                // Toggle only supersampling the pixels on edges.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::E),
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Some(supersampling) = &mut state.supersampling {
+                        supersampling.adaptive = !supersampling.adaptive;
+                        window.request_redraw();
+                    }
+                }
+                // Toggle adding extra samples to the image while the view stays still.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::J),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.temporal_accumulation = !state.temporal_accumulation;
+                    window.request_redraw();
+                }
+                // Toggle dithering the output.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::D),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.dithering = !state.dithering;
+                    window.request_redraw();
+                }
+                // Switch between the Mandelbrot set and the last Julia set picked, keeping both cameras where they were.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::Tab),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.toggle_julia();
+                    update_pixelated(&window, &state, &mut pixelated);
+                    window.request_redraw();
+                }
+                // Cycle through the formulas.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::F),
+                            ..
+                        },
+                    ..
+                } => {
+                    let formula = match state.formula {
+                        Formula::Multibrot(power) if power.get() < 4 => {
+                            Formula::Multibrot(power.next())
+                        }
+                        Formula::Multibrot(_) => Formula::BurningShip,
+                        Formula::BurningShip => Formula::Tricorn,
+                        Formula::Tricorn => Formula::Celtic,
+                        Formula::Celtic => Formula::Buffalo,
+                        Formula::Buffalo => Formula::MANDELBROT,
+                    };
+                    state.set_formula(formula);
+                    update_pixelated(&window, &state, &mut pixelated);
+                    window.request_redraw();
+                }
+                // Toggle the preview of the Julia set under the cursor in the corner of the window.
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(VirtualKeyCode::P),
+                            ..
+                        },
+                    ..
+                } => {
+                    state.set_julia_preview(state.julia_preview.is_none());
+                    state.preview_c = point_under(&state, mouse_offset);
+                    window.request_redraw();
+                }
+                WindowEvent::MouseInput {
+                    button,
+                    state: button_state,
+                    ..
+                } => match (button, button_state) {
                     (MouseButton::Left, ElementState::Pressed) => {
                         dragging = true;
                     }
                     (MouseButton::Left, ElementState::Released) => {
                         dragging = false;
                     }
+                    // Right-clicking a point in the Mandelbrot set opens its Julia set, and right-clicking a Julia set goes back.
+                    (MouseButton::Right, ElementState::Pressed) => {
+                        if state.julia {
+                            state.toggle_julia();
+                        } else {
+                            state.pick_julia(point_under(&state, mouse_offset));
+                        }
+                        update_pixelated(&window, &state, &mut pixelated);
+                        window.request_redraw();
+                    }
                     _ => {}
                 },
                 _ => {}
             },
-            Event::RedrawRequested(_) => state.render(),
+            Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                next_frame = Instant::now() + FRAME_TIME;
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                if state.palette_cycling.is_some() {
+                    let time = Instant::now();
+                    state.cycle_palette((time - last_cycle).as_secs_f32());
+                    last_cycle = time;
+                }
+                // Only the colours change while the palette's cycling, so there's no need to iterate the pixels again.
+                if state.palette_cycling.is_some() && state.iterated() {
+                    state.recolor();
+                    return;
+                }
+                state.render();
+                // Keep redrawing until the iteration limit settles down and the image has converged.
+                if state.update_iterations() || state.accumulating() {
+                    window.request_redraw();
+                }
+            }
             _ => {}
         }
     });
 }
+
+/// The point on the complex plane under the cursor, which is `mouse_offset` physical pixels from the center of the window.
+fn point_under(state: &State, mouse_offset: [f64; 2]) -> Complex {
+    state.camera.clone() + &state.to_complex_offset(mouse_offset)
+}
+
+/// Warns about and shows an indicator in the window title when the image is pixelated because it's zoomed in too far for the available precision.
+fn update_pixelated(window: &Window, state: &State, pixelated: &mut bool) {
+    if state.pixelated() == *pixelated {
+        return;
+    }
+
+    *pixelated = state.pixelated();
+    if *pixelated {
+        log::warn!(
+            "pixels are closer together than {:?} precision can tell apart",
+            state.precision
+        );
+        window.set_title("gpu-mandelbrot (pixelated: out of precision)");
+    } else {
+        window.set_title("gpu-mandelbrot");
+    }
+}
@@ -1,16 +1,75 @@
+use std::cmp;
+use std::cmp::Ordering;
 use std::iter;
 use std::ops::Add;
 use std::ops::AddAssign;
+use std::ops::Div;
 use std::ops::Mul;
+use std::ops::Neg;
 use std::ops::Sub;
 use std::ops::SubAssign;
 
-#[derive(Default, Clone, Debug)]
+/// A signed fixed-point number with an arbitrary number of 32-bit digits after the binary point.
+#[derive(Default, Clone, Debug, PartialEq)]
 pub struct Component {
+    // The integer portion of this fixed-point number, which also controls the sign.
     int: i32,
+    // The sub-integer portion, most significant digit first.
+    // This is _always positive_; so even if `int` is negative, it's added on top.
     subint: Vec<u32>,
 }
 
+impl Component {
+    /// The number of 32-bit digits after the binary point.
+    pub fn precision(&self) -> usize {
+        self.subint.len()
+    }
+
+    /// Extends or truncates the sub-integer portion to `precision` digits.
+    pub fn set_precision(&mut self, precision: usize) {
+        self.subint.resize(precision, 0);
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.int < 0
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // Adding a nearly-1 fraction onto a negative integer would cancel out most of the precision, so do it on the magnitude instead.
+        if self.is_negative() {
+            return -(-self.clone()).to_f64();
+        }
+
+        let mut out = self.int as f64;
+        let mut scale = 1.0;
+        for digit in self.subint.iter().copied() {
+            scale /= 4294967296.0;
+            out += digit as f64 * scale;
+        }
+        out
+    }
+
+    /// Converts this to a big-endian two's complement integer with `precision` limbs after the integer limb, as used by `fixedpoint.wgsl`.
+    ///
+    /// Since the sub-integer portion is always positive, this is just the integer portion followed by it.
+    pub fn to_limbs(&self, precision: usize) -> Vec<u32> {
+        let mut limbs = self.clone();
+        limbs.set_precision(precision);
+        iter::once(limbs.int as u32).chain(limbs.subint).collect()
+    }
+
+    // The absolute value of this component, with the integer portion as the first digit.
+    fn magnitude(&self) -> Vec<u32> {
+        let abs = if self.is_negative() {
+            -self.clone()
+        } else {
+            self.clone()
+        };
+
+        iter::once(abs.int as u32).chain(abs.subint).collect()
+    }
+}
+
 impl Add<&Self> for Component {
     type Output = Self;
 
@@ -45,8 +104,7 @@ impl Add for &Component {
 impl AddAssign<&Self> for Component {
     fn add_assign(&mut self, rhs: &Self) {
         if rhs.subint.len() > self.subint.len() {
-            self.subint
-                .extend(iter::repeat(0).take(rhs.subint.len() - self.subint.len()));
+            self.subint.resize(rhs.subint.len(), 0);
         }
 
         let mut carry = false;
@@ -80,22 +138,24 @@ impl Sub<&Self> for Component {
 impl Sub for Component {
     type Output = Self;
 
-    fn sub(mut self, mut rhs: Self) -> Self::Output {
-        if rhs.subint.len() > self.subint.len() {
-            rhs -= self;
-            rhs
-        } else {
-            self -= rhs;
-            self
-        }
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl Sub for &Component {
+    type Output = Component;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self.clone() - rhs
     }
 }
 
 impl SubAssign<&Self> for Component {
     fn sub_assign(&mut self, rhs: &Self) {
         if rhs.subint.len() > self.subint.len() {
-            self.subint
-                .extend(iter::repeat(0).take(rhs.subint.len() - self.subint.len()));
+            self.subint.resize(rhs.subint.len(), 0);
         }
 
         let mut carry = false;
@@ -117,40 +177,60 @@ impl SubAssign for Component {
     }
 }
 
+impl Neg for Component {
+    type Output = Self;
+
+    fn neg(mut self) -> Self::Output {
+        // Two's complement negation: flip every bit, then add 1 to the lowest digit.
+        let mut carry = true;
+
+        for digit in self.subint.iter_mut().rev() {
+            let (res, new_carry) = (!*digit).carrying_add(0, carry);
+            carry = new_carry;
+            *digit = res;
+        }
+
+        self.int = (!self.int).wrapping_add(carry as i32);
+        self
+    }
+}
+
 impl Mul for &Component {
     type Output = Component;
 
+    /// Multiplies two components, truncating the result to the larger of their precisions.
     fn mul(self, rhs: Self) -> Self::Output {
-        let out_subint_len = self.subint.len() + rhs.subint.len();
+        let precision = cmp::max(self.precision(), rhs.precision());
+        let negative = self.is_negative() != rhs.is_negative();
+
+        let a = self.magnitude();
+        let b = rhs.magnitude();
+
+        // Primary-school style multiplication of the magnitudes, treating them as big integers.
+        // The product has two integer digits, followed by `a.len() + b.len() - 2` sub-integer digits.
+        let mut product = vec![0; a.len() + b.len()];
+        for (i, digit_a) in a.iter().copied().enumerate().rev() {
+            let mut carry = 0;
+            for (j, digit_b) in b.iter().copied().enumerate().rev() {
+                // This can't overflow: (2^32 - 1)^2 + 2 * (2^32 - 1) = 2^64 - 1.
+                let res = digit_a as u64 * digit_b as u64 + product[i + j + 1] as u64 + carry;
+                product[i + j + 1] = res as u32;
+                carry = res >> 32;
+            }
+            product[i] = carry as u32;
+        }
 
-        let mut out = Component {
-            int: 0,
-            subint: vec![0; out_subint_len],
+        // Anything which doesn't fit in the `i32` is discarded; everything is assumed to wrap on overflow.
+        let out = Component {
+            int: product[1] as i32,
+            subint: product[2..2 + precision].to_vec(),
         };
 
-        // The carry from the actual multiplication
-        let mut carry = 0;
-        // The carry from adding to the result
-        let mut bitcarry = false;
-
-        for (i, digit_a) in self.subint.iter().copied().rev().enumerate() {
-            for (digit_b, target) in rhs
-                .subint
-                .iter()
-                .copied()
-                .rev()
-                .chain(iter::repeat(0))
-                .zip(out.subint[..=out_subint_len - i].iter_mut().rev())
-            {
-                let (res, new_carry) = digit_a.carrying_mul(digit_b, carry);
-                carry = new_carry;
-                let (new_target, new_bitcarry) = target.carrying_add(res, bitcarry);
-                *target = new_target;
-                bitcarry = new_bitcarry;
-            }
+        if negative {
+            -out
+        } else {
+            out
         }
-
-        out
     }
 }
 
@@ -162,56 +242,87 @@ impl Mul for Component {
     }
 }
 
-impl From<f32> for Component {
-    fn from(num: f32) -> Self {
+impl From<f64> for Component {
+    fn from(num: f64) -> Self {
         if num.is_infinite() {
             panic!("`Component`s cannot be infinite");
         } else if num.is_nan() {
             panic!("`Component`s cannot be NaN");
         }
 
-        let int = num.floor();
-        let subint = num - num.floor();
+        // Subtracting the integer portion from a negative number isn't exact, so convert the magnitude instead.
+        if num < 0.0 {
+            return -Self::from(-num);
+        }
 
-        let exponent = (int.to_bits() >> 23) as u8;
-        // The mantissa of a float is effectively 24 bits,
-        // so you can only shift it up at most 7 bits whilst having it fit in an i32 (since the first bit is a sign bit)
-        if exponent as i16 - 127 > 7 {
+        let int = num.floor();
+        if int > i32::MAX as f64 {
             panic!("{} is too large to fit in a `Component`", num);
         }
-        // We've now checked that this will fit.
-        let int = int as i32;
 
-        let bits = subint.to_bits();
-        let exponent = (bits >> 23) as u8;
-        let fraction = 1 << 31 | (bits & (u32::MAX >> 9)) << 8;
+        // Peel off 32 bits of the fractional part at a time.
+        // Multiplying by a power of two and subtracting the integer part are both exact, so no precision is lost.
+        let mut subint = Vec::new();
+        let mut fraction = num - int;
+        while fraction != 0.0 {
+            fraction *= 4294967296.0;
+            let digit = fraction.floor();
+            subint.push(digit as u32);
+            fraction -= digit;
+        }
 
-        // How many bits the subint is offset from the start of the subint portion.
-        // offset = -1 - (exponent - 127)
-        //        = 126 - exponent
-        let offset = 126 - exponent;
+        Self {
+            int: int as i32,
+            subint,
+        }
+    }
+}
 
-        // The number of bits needed for the subint.
-        // -1 - (exponent - 127) + 24 - trailing_zeros
-        // 126 - exponent + 24 - trailing_zeros
-        // 150 - exponent - trailing_zeros
-        let subint_bits =
-            usize::from(offset + 24) - fraction.trailing_zeros().clamp(0, 23) as usize;
+impl From<f32> for Component {
+    fn from(num: f32) -> Self {
+        // Every `f32` is exactly representable as an `f64`.
+        Self::from(num as f64)
+    }
+}
 
-        let mut out = Self {
-            int,
-            subint: vec![0; subint_bits / 50],
-        };
+impl From<FloatExp> for Component {
+    fn from(num: FloatExp) -> Self {
+        if num.mantissa < 0.0 {
+            return -Self::from(-num);
+        } else if num.mantissa == 0.0 {
+            return Self::default();
+        }
 
-        let idx = usize::from(offset) / 32;
-        let suboffset = offset % 32;
-        out.subint[idx] = fraction >> suboffset;
-        // We won't have allocated room for the next digit if we didn't need it.
-        if idx + 1 < out.subint.len() {
-            out.subint[idx + 1] = fraction << (32 - suboffset);
+        // Turn the mantissa into a 53-bit integer, so that the value is `mantissa * 2^-fraction_bits`.
+        let mantissa = (num.mantissa * (1u64 << 53) as f64) as u64;
+        let fraction_bits = 53 - num.exponent;
+
+        if fraction_bits <= 0 {
+            let int = (mantissa as u128)
+                .checked_shl(-fraction_bits as u32)
+                .filter(|&int| int <= i32::MAX as u128)
+                .unwrap_or_else(|| panic!("{:?} is too large to fit in a `Component`", num));
+            return Self::from(int as i32);
         }
 
-        out
+        // Shift the mantissa so that the binary point lands on a digit boundary, and then split it up into digits from the bottom.
+        let precision = (fraction_bits as usize).div_ceil(32);
+        let mut bits = (mantissa as u128) << (32 * precision as i64 - fraction_bits);
+
+        let mut subint = vec![0; precision];
+        for digit in subint.iter_mut().rev() {
+            *digit = bits as u32;
+            bits >>= 32;
+        }
+
+        if bits > i32::MAX as u128 {
+            panic!("{:?} is too large to fit in a `Component`", num);
+        }
+
+        Self {
+            int: bits as i32,
+            subint,
+        }
     }
 }
 
@@ -224,17 +335,612 @@ impl From<i32> for Component {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Complex {
     pub real: Component,
     pub imag: Component,
 }
 
 impl Complex {
+    pub fn new(real: Component, imag: Component) -> Self {
+        Self { real, imag }
+    }
+
     pub fn square(&self) -> Self {
-        let real = (self.real.clone() + &self.imag) * (self.real.clone() - &self.imag);
-        let mut imag = self.real.clone() + &self.imag;
+        // (a + bi)^2 = (a + b)(a - b) + 2abi
+        let real = (&self.real + &self.imag) * (&self.real - &self.imag);
+        let mut imag = &self.real * &self.imag;
         imag += imag.clone();
         Self { real, imag }
     }
+
+    /// Extends or truncates both components to `precision` digits.
+    pub fn set_precision(&mut self, precision: usize) {
+        self.real.set_precision(precision);
+        self.imag.set_precision(precision);
+    }
+
+    /// Rounds this number to a pair of `f32`s, ready to be sent to the GPU.
+    pub fn to_f32(&self) -> [f32; 2] {
+        [self.real.to_f64() as f32, self.imag.to_f64() as f32]
+    }
+}
+
+impl From<[f64; 2]> for Complex {
+    fn from([real, imag]: [f64; 2]) -> Self {
+        Self {
+            real: real.into(),
+            imag: imag.into(),
+        }
+    }
+}
+
+impl Add<&Self> for Complex {
+    type Output = Self;
+
+    fn add(mut self, rhs: &Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl AddAssign<&Self> for Complex {
+    fn add_assign(&mut self, rhs: &Self) {
+        self.real += &rhs.real;
+        self.imag += &rhs.imag;
+    }
+}
+
+impl Sub<&Self> for Complex {
+    type Output = Self;
+
+    fn sub(mut self, rhs: &Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl SubAssign<&Self> for Complex {
+    fn sub_assign(&mut self, rhs: &Self) {
+        self.real -= &rhs.real;
+        self.imag -= &rhs.imag;
+    }
+}
+
+/// A floating point number with an extended exponent range, `mantissa * 2^exponent`,
+/// for numbers too small or large to fit in an `f64` (like the zoom level or pixel size at deep zooms).
+///
+/// The mantissa is always either 0 or between 0.5 and 1 in magnitude.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FloatExp {
+    mantissa: f64,
+    exponent: i64,
+}
+
+impl FloatExp {
+    pub fn new(mantissa: f64, exponent: i64) -> Self {
+        if mantissa == 0.0 || !mantissa.is_finite() {
+            return Self {
+                mantissa,
+                exponent: 0,
+            };
+        }
+
+        // Pull the exponent out of the mantissa's bits, scaling subnormals up first so that they have one.
+        let (mantissa, exponent) = if mantissa.abs() < f64::MIN_POSITIVE {
+            (mantissa * 2f64.powi(64), exponent - 64)
+        } else {
+            (mantissa, exponent)
+        };
+        let shift = ((mantissa.to_bits() >> 52) & 0x7ff) as i64 - 1022;
+
+        Self {
+            mantissa: f64::from_bits((mantissa.to_bits() & !(0x7ff << 52)) | (1022 << 52)),
+            exponent: exponent + shift,
+        }
+    }
+
+    pub fn mantissa(&self) -> f64 {
+        self.mantissa
+    }
+
+    pub fn exponent(&self) -> i64 {
+        self.exponent
+    }
+
+    pub fn to_f64(self) -> f64 {
+        // Split up the scaling so that the intermediate power of two doesn't overflow before the mantissa brings it back in range.
+        let exponent = self.exponent.clamp(-2200, 2200) as i32;
+        self.mantissa * 2f64.powi(exponent / 2) * 2f64.powi(exponent - exponent / 2)
+    }
+
+    /// Splits this number into an `f32` mantissa and `i32` exponent, to be sent to the GPU.
+    pub fn to_f32_parts(self) -> (f32, i32) {
+        (self.mantissa as f32, self.exponent as i32)
+    }
+
+    pub fn abs(self) -> Self {
+        Self {
+            mantissa: self.mantissa.abs(),
+            exponent: self.exponent,
+        }
+    }
+
+    pub fn sqrt(self) -> Self {
+        // Make the exponent even so that it can be halved exactly.
+        Self::new(
+            (self.mantissa * 2f64.powi(self.exponent.rem_euclid(2) as i32)).sqrt(),
+            self.exponent.div_euclid(2),
+        )
+    }
+
+    pub fn log2(self) -> f64 {
+        self.mantissa.log2() + self.exponent as f64
+    }
+}
+
+impl From<f64> for FloatExp {
+    fn from(num: f64) -> Self {
+        Self::new(num, 0)
+    }
+}
+
+impl From<&Component> for FloatExp {
+    fn from(num: &Component) -> Self {
+        if num.is_negative() {
+            return -Self::from(&-num.clone());
+        }
+
+        // An `f64` only has room for 53 bits of the number, so only bother looking at the first 3 non-zero digits.
+        let digits = num.magnitude();
+        let first = match digits.iter().position(|&digit| digit != 0) {
+            Some(first) => first,
+            None => return Self::default(),
+        };
+
+        let mantissa = digits[first..]
+            .iter()
+            .take(3)
+            .rev()
+            .fold(0.0, |acc, &digit| acc / 4294967296.0 + digit as f64);
+
+        Self::new(mantissa, -32 * first as i64)
+    }
+}
+
+impl Neg for FloatExp {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self {
+            mantissa: -self.mantissa,
+            exponent: self.exponent,
+        }
+    }
+}
+
+impl Add for FloatExp {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        if self.mantissa == 0.0 {
+            return rhs;
+        } else if rhs.mantissa == 0.0 {
+            return self;
+        }
+
+        // Line up the smaller number with the larger one's exponent; if it's too small to matter, it just becomes 0.
+        let (larger, smaller) = if self.exponent >= rhs.exponent {
+            (self, rhs)
+        } else {
+            (rhs, self)
+        };
+        let shift = (smaller.exponent - larger.exponent).max(-1100) as i32;
+
+        Self::new(
+            larger.mantissa + smaller.mantissa * 2f64.powi(shift),
+            larger.exponent,
+        )
+    }
+}
+
+impl Sub for FloatExp {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + -rhs
+    }
+}
+
+impl Mul for FloatExp {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(self.mantissa * rhs.mantissa, self.exponent + rhs.exponent)
+    }
+}
+
+impl Div for FloatExp {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        Self::new(self.mantissa / rhs.mantissa, self.exponent - rhs.exponent)
+    }
+}
+
+impl PartialOrd for FloatExp {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (*self - *other).mantissa.partial_cmp(&0.0)
+    }
+}
+
+/// A number represented as the unevaluated sum of two `f32`s, `hi + lo`, giving about 48 bits of precision.
+///
+/// This does exactly the same operations as `doublesingle.wgsl`, so that the GPU's results can be checked against it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DoubleSingle {
+    pub hi: f32,
+    pub lo: f32,
+}
+
+impl DoubleSingle {
+    pub fn new(hi: f32, lo: f32) -> Self {
+        Self { hi, lo }
+    }
+
+    /// Adds two `f32`s, returning the rounded sum and the rounding error.
+    pub fn two_sum(a: f32, b: f32) -> Self {
+        let sum = a + b;
+        let b_virtual = sum - a;
+        let a_virtual = sum - b_virtual;
+        Self::new(sum, (a - a_virtual) + (b - b_virtual))
+    }
+
+    /// Like `two_sum`, but only correct if |a| >= |b|.
+    fn quick_two_sum(a: f32, b: f32) -> Self {
+        let sum = a + b;
+        Self::new(sum, b - (sum - a))
+    }
+
+    /// Splits an `f32` into two halves with 12 bits of mantissa each, so that multiplying them together is exact.
+    fn split(a: f32) -> (f32, f32) {
+        // 2^12 + 1
+        let t = 4097.0 * a;
+        let hi = t - (t - a);
+        (hi, a - hi)
+    }
+
+    /// Multiplies two `f32`s, returning the rounded product and the rounding error.
+    pub fn two_product(a: f32, b: f32) -> Self {
+        let product = a * b;
+        let (a_hi, a_lo) = Self::split(a);
+        let (b_hi, b_lo) = Self::split(b);
+        let error = ((a_hi * b_hi - product) + a_hi * b_lo + a_lo * b_hi) + a_lo * b_lo;
+        Self::new(product, error)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.hi as f64 + self.lo as f64
+    }
+}
+
+impl From<f64> for DoubleSingle {
+    fn from(num: f64) -> Self {
+        let hi = num as f32;
+        Self::new(hi, (num - hi as f64) as f32)
+    }
+}
+
+impl From<&Component> for DoubleSingle {
+    fn from(num: &Component) -> Self {
+        let hi = num.to_f64() as f32;
+        let lo = (num.clone() - Component::from(hi)).to_f64() as f32;
+        Self::new(hi, lo)
+    }
+}
+
+impl Neg for DoubleSingle {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.hi, -self.lo)
+    }
+}
+
+impl Add for DoubleSingle {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let high = Self::two_sum(self.hi, rhs.hi);
+        let low = Self::two_sum(self.lo, rhs.lo);
+        let sum = Self::quick_two_sum(high.hi, high.lo + low.hi);
+        Self::quick_two_sum(sum.hi, sum.lo + low.lo)
+    }
+}
+
+impl Sub for DoubleSingle {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        self + -rhs
+    }
+}
+
+impl Mul for DoubleSingle {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let product = Self::two_product(self.hi, rhs.hi);
+        Self::quick_two_sum(
+            product.hi,
+            product.lo + (self.hi * rhs.lo + self.lo * rhs.hi),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pairs of numbers which are awkward for double-single arithmetic:
+    /// ones which nearly cancel out, ones an `f32` ulp apart, and ones of very different sizes.
+    const HARD_CASES: &[(f64, f64)] = &[
+        (1.0, 1e-9),
+        (1.0 + 1.0 / (1 << 30) as f64, -1.0),
+        (
+            1.0 + f32::EPSILON as f64,
+            -(1.0 - f32::EPSILON as f64 / 2.0),
+        ),
+        (0.1, 0.2),
+        (-1.749_999_999_999_1, 1.75),
+        (std::f64::consts::PI, -std::f64::consts::E),
+        (123_456.789_012_345, 0.000_012_345_678_9),
+        (1.0 / 3.0, 2.0 / 3.0),
+    ];
+
+    /// Whether `actual` is within `bits` bits of precision of `expected`.
+    fn close(actual: DoubleSingle, expected: f64, scale: f64, bits: i32) -> bool {
+        (actual.to_f64() - expected).abs() <= scale.abs() * 2f64.powi(-bits)
+    }
+
+    #[test]
+    fn two_sum_is_exact() {
+        for &(a, b) in HARD_CASES {
+            let (a, b) = (a as f32, b as f32);
+            let sum = DoubleSingle::two_sum(a, b);
+            assert_eq!(sum.hi, a + b);
+            assert_eq!(sum.to_f64(), a as f64 + b as f64, "{} + {}", a, b);
+        }
+    }
+
+    #[test]
+    fn two_product_is_exact() {
+        for &(a, b) in HARD_CASES {
+            let (a, b) = (a as f32, b as f32);
+            let product = DoubleSingle::two_product(a, b);
+            assert_eq!(product.hi, a * b);
+            // The product of two `f32`s has at most 48 significant bits, so it's exact as an `f64`.
+            assert_eq!(product.to_f64(), a as f64 * b as f64, "{} * {}", a, b);
+        }
+    }
+
+    #[test]
+    fn from_f64_round_trips() {
+        for &(a, _) in HARD_CASES {
+            let ds = DoubleSingle::from(a);
+            assert!(close(ds, a, a, 47), "{} became {:?}", a, ds);
+            // The low half has to be small enough not to change the high half.
+            assert_eq!(ds.hi + ds.lo, ds.hi);
+        }
+    }
+
+    #[test]
+    fn add_matches_f64() {
+        for &(a, b) in HARD_CASES {
+            let sum = DoubleSingle::from(a) + DoubleSingle::from(b);
+            // Rounding the inputs loses up to 2^-48 of each, which cancellation can't magnify past the larger input.
+            let scale = a.abs().max(b.abs());
+            assert!(close(sum, a + b, scale, 46), "{} + {} gave {:?}", a, b, sum);
+        }
+    }
+
+    #[test]
+    fn sub_matches_f64() {
+        for &(a, b) in HARD_CASES {
+            let difference = DoubleSingle::from(a) - DoubleSingle::from(b);
+            let scale = a.abs().max(b.abs());
+            assert!(
+                close(difference, a - b, scale, 46),
+                "{} - {} gave {:?}",
+                a,
+                b,
+                difference
+            );
+        }
+    }
+
+    #[test]
+    fn mul_matches_f64() {
+        for &(a, b) in HARD_CASES {
+            let product = DoubleSingle::from(a) * DoubleSingle::from(b);
+            assert!(
+                close(product, a * b, a * b, 44),
+                "{} * {} gave {:?}",
+                a,
+                b,
+                product
+            );
+        }
+    }
+
+    /// Numbers which need more than one digit after the binary point, and ones with every bit of a digit set, so that carries ripple.
+    const COMPONENT_CASES: &[f64] = &[
+        1.5,
+        2.25,
+        std::f64::consts::PI,
+        1.0 - 1.0 / 4294967296.0,
+        0.999_999_999_999_999_9,
+        123.456_789,
+        1.0 / 3.0,
+    ];
+
+    #[test]
+    fn neg_is_twos_complement() {
+        let negated = -Component::from(0.25);
+        assert_eq!(negated.int, -1);
+        assert_eq!(negated.subint, [0xc000_0000]);
+
+        // Negating 0 carries all the way through without changing anything.
+        let zero = Component {
+            int: 0,
+            subint: vec![0; 3],
+        };
+        assert_eq!(-zero.clone(), zero);
+
+        for &a in COMPONENT_CASES {
+            let component = Component::from(a);
+            assert_eq!(-(-component.clone()), component);
+            assert_eq!((-component).int, -(a.ceil() as i32), "-{}", a);
+        }
+    }
+
+    #[test]
+    fn mul_signs_match_f64() {
+        for &a in COMPONENT_CASES {
+            for &b in COMPONENT_CASES {
+                for (a, b) in [(a, b), (-a, b), (a, -b), (-a, -b)] {
+                    let product = Component::from(a) * Component::from(b);
+                    // The product is truncated to the longer input's precision.
+                    let ulp = 2f64.powi(-32 * product.precision() as i32);
+                    assert!(
+                        (product.to_f64() - a * b).abs() <= ulp + (a * b).abs() * 1e-15,
+                        "{} * {} gave {}",
+                        a,
+                        b,
+                        product.to_f64()
+                    );
+                    assert_eq!(product.is_negative(), (a < 0.0) != (b < 0.0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mul_carries() {
+        // (1 - 2^-32)² = 1 - 2^-31 + 2^-64, which truncates to 1 - 2^-31 in one digit.
+        let almost_one = Component::from(1.0 - 1.0 / 4294967296.0);
+        let square = &almost_one * &almost_one;
+        assert_eq!(square.int, 0);
+        assert_eq!(square.subint, [0xffff_fffe]);
+
+        // The same magnitude truncated towards 0 on the other side.
+        let negative = &(-almost_one.clone()) * &almost_one;
+        assert_eq!(negative, -square);
+
+        // The fractional digit of (2^8 - 2^-32)² carries into the integer digit.
+        let big = Component::from(256.0 - 1.0 / 4294967296.0);
+        let product = &big * &big;
+        assert_eq!(product.int, 65535);
+        assert_eq!(product.subint, [0xffff_fe00]);
+    }
+
+    #[test]
+    fn keeps_what_f32_loses() {
+        // 1 + 2^-30 isn't representable as an `f32`, so plain f32 arithmetic gets 0 here.
+        let tiny = 1.0 / (1u64 << 30) as f64;
+        let sum = DoubleSingle::from(1.0) + DoubleSingle::from(tiny);
+        assert_eq!(sum.hi, 1.0);
+        assert_eq!((sum - DoubleSingle::from(1.0)).to_f64(), tiny);
+    }
+
+    #[test]
+    fn float_exp_normalizes() {
+        let three = FloatExp::new(3.0, 10);
+        assert_eq!((three.mantissa(), three.exponent()), (0.75, 12));
+
+        let negative = FloatExp::new(-0.1, 0);
+        assert_eq!(negative.mantissa(), -0.8);
+        assert_eq!(negative.exponent(), -3);
+
+        // Subnormals don't have an exponent of their own, so they need scaling up before it can be pulled out.
+        let subnormal = FloatExp::from(f64::MIN_POSITIVE / 8.0);
+        assert_eq!((subnormal.mantissa(), subnormal.exponent()), (0.5, -1024));
+        assert_eq!(subnormal.to_f64(), f64::MIN_POSITIVE / 8.0);
+        let smallest = FloatExp::from(-f64::from_bits(1));
+        assert_eq!((smallest.mantissa(), smallest.exponent()), (-0.5, -1073));
+
+        // 0 has no exponent, whatever it's given.
+        for zero in [FloatExp::new(0.0, 5000), FloatExp::new(-0.0, -5000)] {
+            assert_eq!(zero.mantissa(), 0.0);
+            assert_eq!(zero.exponent(), 0);
+            assert_eq!(zero, FloatExp::default());
+        }
+    }
+
+    #[test]
+    fn float_exp_add_sub_across_exponents() {
+        let one = FloatExp::from(1.0);
+        let tiny = FloatExp::new(1.0, -2000);
+
+        // Far too small to change the larger number, in either order.
+        assert_eq!(one + tiny, one);
+        assert_eq!(tiny + one, one);
+        assert_eq!(one - tiny, one);
+        assert_eq!(tiny - one, -one);
+
+        // Close enough together to add exactly, far past what an `f64` can hold.
+        assert_eq!(tiny + tiny, FloatExp::new(1.0, -1999));
+        assert_eq!(tiny + FloatExp::new(1.0, -2001), FloatExp::new(1.5, -2000));
+        assert_eq!(
+            FloatExp::new(0.75, 5000) - FloatExp::new(0.5, 4999),
+            FloatExp::new(0.5, 5000)
+        );
+        assert_eq!(
+            (one + FloatExp::new(1.0, -30)).to_f64(),
+            1.0 + 1.0 / (1u64 << 30) as f64
+        );
+
+        // Cancelling out gives a normalized 0.
+        assert_eq!(tiny - tiny, FloatExp::default());
+        assert_eq!(
+            FloatExp::new(-0.5, 3000) + FloatExp::new(0.5, 3000),
+            FloatExp::default()
+        );
+    }
+
+    #[test]
+    fn float_exp_orders_negatives_and_zero() {
+        let ascending = [
+            FloatExp::new(-0.5, 3000),
+            FloatExp::from(-2.0),
+            FloatExp::from(-0.5),
+            FloatExp::new(-0.5, -3000),
+            FloatExp::default(),
+            FloatExp::new(0.5, -3000),
+            FloatExp::from(0.5),
+            FloatExp::from(2.0),
+            FloatExp::new(0.5, 3000),
+        ];
+        for (i, a) in ascending.iter().enumerate() {
+            for (j, b) in ascending.iter().enumerate() {
+                assert_eq!(a.partial_cmp(b), Some(i.cmp(&j)), "{:?} vs {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn float_exp_component_round_trips() {
+        for exponent in [-1000, -300, -64, -33, -32, -1, 0, 20] {
+            for mantissa in [0.5, -0.5, 0.6, -0.7, 0.999_999_999_999_999_9] {
+                let num = FloatExp::new(mantissa, exponent);
+                let component = Component::from(num);
+                // Every one of the mantissa's 53 bits needs to fit in whole digits after the binary point.
+                assert_eq!(
+                    component.precision(),
+                    ((53 - exponent) as usize).div_ceil(32)
+                );
+                assert_eq!(FloatExp::from(&component), num, "{:?}", num);
+            }
+        }
+    }
 }
@@ -0,0 +1,381 @@
+//! Colourings which build up a value along each pixel's whole orbit, rather than only looking at where it ended up.
+//!
+//! Orbit traps colour pixels by how close their orbit ever got to some shape, and stripe average colouring by the average of
+//! `sin(frequency × arg z)` over the orbit, interpolated between the last two averages so that it's continuous.
+//!
+//! Atom domains are the iteration n where |z_n| was smallest. Each minibrot of period p sits in a domain of points whose
+//! smallest |z| was at iteration p, so the domains show where the minibrots are. Misiurewicz domains are the same thing for preperiodic
+//! points: the iteration q where |z_{q+1} - z_q| was smallest, which is near the preperiod of the nearest Misiurewicz point
+//! whose period is 1.
+//!
+//! These need to see every iteration, so series approximation and BLA are turned off while they're being used.
+//! This mirrors what the iteration shaders do, so that images rendered on the CPU come out the same.
+
+use crate::formula::Formula;
+use crate::Pixel;
+
+/// The flags for which values need building up along each orbit (the `TRACK_*` constants in `common.wgsl`).
+pub const TRACK_TRAP: u32 = 1;
+pub const TRACK_STRIPES: u32 = 2;
+pub const TRACK_ATOM: u32 = 4;
+pub const TRACK_MISIUREWICZ: u32 = 8;
+
+/// The default stripe frequency, which gives a few stripes per ring of the set's outline.
+pub const DEFAULT_STRIPE_FREQUENCY: f32 = 5.0;
+
+/// What an orbit trap's shape is (the `TRAP_*` constants in `common.wgsl`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapShape {
+    /// A circle around `center` with a radius of `radius`, which is a single point if that's 0.
+    Point = 1,
+    /// A line through `center` at an angle of `angle`.
+    Line = 2,
+    /// Two perpendicular lines through `center`, the first of which is at an angle of `angle`.
+    Cross = 3,
+}
+
+/// A shape on the complex plane which pixels are coloured by how close their orbits get to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitTrap {
+    pub shape: TrapShape,
+    pub center: [f32; 2],
+    /// The angle of the trap's line(s), in radians anticlockwise from the real axis.
+    pub angle: f32,
+    /// The radius of a `Point` trap.
+    pub radius: f32,
+}
+
+impl Default for OrbitTrap {
+    fn default() -> Self {
+        Self {
+            shape: TrapShape::Point,
+            center: [0.0, 0.0],
+            angle: 0.0,
+            radius: 0.0,
+        }
+    }
+}
+
+impl OrbitTrap {
+    /// The distance from `z` to the trap.
+    pub fn distance(&self, z: [f32; 2]) -> f32 {
+        let offset = [z[0] - self.center[0], z[1] - self.center[1]];
+        // The offset rotated so that the trap's line lies along the real axis.
+        let (sin, cos) = self.angle.sin_cos();
+        let along = offset[0] * cos + offset[1] * sin;
+        let across = offset[1] * cos - offset[0] * sin;
+
+        match self.shape {
+            TrapShape::Point => (along.hypot(across) - self.radius).abs(),
+            TrapShape::Line => across.abs(),
+            TrapShape::Cross => across.abs().min(along.abs()),
+        }
+    }
+}
+
+/// The values built up along an orbit so far (`OrbitStats` in `common.wgsl`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitStats {
+    /// The closest the orbit has gotten to the trap.
+    pub trap: f32,
+    /// The sum of the stripe values of every point in the orbit.
+    pub stripe_sum: f32,
+    /// The stripe value of the last point, for interpolating between the last two averages.
+    pub stripe_last: f32,
+    /// The atom domain so far, and the |z|² it had there.
+    pub atom: u32,
+    pub atom_norm: f32,
+    /// The Misiurewicz domain so far, and the |z_{q+1} - z_q|² it had there.
+    pub misiurewicz: u32,
+    pub misiurewicz_norm: f32,
+    /// The previous point of the orbit.
+    pub previous: [f32; 2],
+}
+
+impl Default for OrbitStats {
+    fn default() -> Self {
+        Self {
+            trap: f32::MAX,
+            stripe_sum: 0.0,
+            stripe_last: 0.0,
+            atom: 0,
+            atom_norm: f32::MAX,
+            misiurewicz: 0,
+            misiurewicz_norm: f32::MAX,
+            previous: [0.0; 2],
+        }
+    }
+}
+
+impl OrbitStats {
+    /// Adds `z`, the `n`th point of the orbit.
+    pub fn add(&mut self, z: [f32; 2], n: u32, trap: &OrbitTrap, stripe_frequency: f32) {
+        self.trap = self.trap.min(trap.distance(z));
+        self.stripe_last = stripe_value(z, stripe_frequency);
+        self.stripe_sum += self.stripe_last;
+
+        let norm = z[0] * z[0] + z[1] * z[1];
+        if norm < self.atom_norm {
+            self.atom = n;
+            self.atom_norm = norm;
+        }
+
+        // z₀ = 0 and z₁ = c, so the preperiod has to be at least 1.
+        let difference = [z[0] - self.previous[0], z[1] - self.previous[1]];
+        let norm = difference[0] * difference[0] + difference[1] * difference[1];
+        if n >= 2 && norm < self.misiurewicz_norm {
+            self.misiurewicz = n - 1;
+            self.misiurewicz_norm = norm;
+        }
+        self.previous = z;
+    }
+
+    /// The stripe average of an orbit which escaped after `iters` iterations with |z|² = `norm`,
+    /// blended between the averages with and without the last point by the fractional part of the smooth iteration count,
+    /// which needs the formula's `Formula::log_degree` as `log_degree`.
+    pub fn stripe_average(&self, iters: u32, norm: f32, bailout: f32, log_degree: f32) -> f32 {
+        let average = self.stripe_sum / iters as f32;
+        let previous = if iters > 1 {
+            (self.stripe_sum - self.stripe_last) / (iters - 1) as f32
+        } else {
+            average
+        };
+
+        let ratio = norm.ln() / (bailout * bailout).ln();
+        let fraction = 1.0 - ratio.max(1.0).ln() / log_degree;
+        previous + fraction * (average - previous)
+    }
+}
+
+/// The value stripe average colouring adds up for `z`, which is between 0 and 1.
+pub fn stripe_value(z: [f32; 2], frequency: f32) -> f32 {
+    0.5 * (frequency * z[1].atan2(z[0])).sin() + 0.5
+}
+
+/// Iterates the point `c` directly in `f64`, tracking its orbit for `trap`, stripe average and domain colouring.
+///
+/// This is a CPU reference for checking the iteration shaders' `trap`, `stripe`, `atom` and `misiurewicz` results against,
+/// so `slope` and `distance` are left out.
+pub fn iterate(
+    c: [f64; 2],
+    iterations: u32,
+    bailout: f32,
+    trap: &OrbitTrap,
+    stripe_frequency: f32,
+) -> Pixel {
+    let mut z = [0.0f64; 2];
+    let mut stats = OrbitStats::default();
+    let mut iters = 0;
+    let mut norm = 0.0;
+    while iters < iterations {
+        z = [z[0] * z[0] - z[1] * z[1] + c[0], 2.0 * z[0] * z[1] + c[1]];
+        iters += 1;
+
+        stats.add([z[0] as f32, z[1] as f32], iters, trap, stripe_frequency);
+
+        norm = z[0] * z[0] + z[1] * z[1];
+        if norm >= bailout as f64 * bailout as f64 {
+            break;
+        }
+    }
+
+    let (stripe, angle) = if iters < iterations {
+        (
+            stats.stripe_average(
+                iters,
+                norm as f32,
+                bailout,
+                Formula::MANDELBROT.log_degree(),
+            ),
+            z[1].atan2(z[0]) as f32,
+        )
+    } else {
+        (0.0, 0.0)
+    };
+
+    Pixel {
+        iters,
+        norm: norm as f32,
+        trap: stats.trap,
+        stripe,
+        angle,
+        atom: stats.atom,
+        misiurewicz: stats.misiurewicz,
+        ..Pixel::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coloring::DEFAULT_BAILOUT;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn trap_distances() {
+        let point = OrbitTrap {
+            shape: TrapShape::Point,
+            center: [1.0, 1.0],
+            radius: 0.5,
+            ..OrbitTrap::default()
+        };
+        assert!(close(point.distance([1.0, 1.0]), 0.5));
+        assert!(close(point.distance([4.0, 5.0]), 4.5));
+        assert!(close(point.distance([1.5, 1.0]), 0.0));
+
+        let line = OrbitTrap {
+            shape: TrapShape::Line,
+            angle: std::f32::consts::FRAC_PI_4,
+            ..OrbitTrap::default()
+        };
+        // The line y = x.
+        assert!(close(line.distance([3.0, 3.0]), 0.0));
+        assert!(close(line.distance([1.0, 0.0]), 0.5f32.sqrt()));
+
+        let cross = OrbitTrap {
+            shape: TrapShape::Cross,
+            center: [0.0, 1.0],
+            ..OrbitTrap::default()
+        };
+        assert!(close(cross.distance([0.25, 3.0]), 0.25));
+        assert!(close(cross.distance([5.0, 0.5]), 0.5));
+    }
+
+    #[test]
+    fn trap_is_the_closest_approach() {
+        let trap = OrbitTrap {
+            center: [0.3, -0.2],
+            ..OrbitTrap::default()
+        };
+        let c = [-0.2, 0.7];
+        let pixel = iterate(c, 50, DEFAULT_BAILOUT, &trap, DEFAULT_STRIPE_FREQUENCY);
+
+        let mut z = [0.0f64; 2];
+        let mut closest = f32::MAX;
+        for _ in 0..pixel.iters {
+            z = [z[0] * z[0] - z[1] * z[1] + c[0], 2.0 * z[0] * z[1] + c[1]];
+            closest = closest.min(trap.distance([z[0] as f32, z[1] as f32]));
+        }
+        assert!(close(pixel.trap, closest));
+        // z₁ = c, so it can't be any further than c is.
+        assert!(pixel.trap <= trap.distance([c[0] as f32, c[1] as f32]));
+    }
+
+    #[test]
+    fn stripe_average_is_bounded() {
+        let trap = OrbitTrap::default();
+        for i in 0..200 {
+            let angle = i as f64 * 0.1;
+            let c = [
+                2.5 * angle.cos() - 0.5,
+                2.5 * angle.sin() * (i as f64 / 200.0),
+            ];
+            for &frequency in &[1.0, DEFAULT_STRIPE_FREQUENCY, 12.0] {
+                let pixel = iterate(c, 500, DEFAULT_BAILOUT, &trap, frequency);
+                if pixel.iters < 500 {
+                    assert!(
+                        (0.0..=1.0).contains(&pixel.stripe),
+                        "stripe average of {:?} was {}",
+                        c,
+                        pixel.stripe
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stripe_average_interpolates() {
+        let mut stats = OrbitStats::default();
+        let trap = OrbitTrap::default();
+        stats.add([1.0, 0.0], 1, &trap, 1.0);
+        stats.add([0.0, 1.0], 2, &trap, 1.0);
+        // The values are 0.5 and 1, so the averages with and without the last one are 0.75 and 0.5.
+        let bailout = DEFAULT_BAILOUT;
+        assert!(close(
+            stats.stripe_average(
+                2,
+                bailout * bailout,
+                bailout,
+                Formula::MANDELBROT.log_degree()
+            ),
+            0.75
+        ));
+        assert!(close(
+            stats.stripe_average(
+                2,
+                bailout.powi(4),
+                bailout,
+                Formula::MANDELBROT.log_degree()
+            ),
+            0.5
+        ));
+    }
+
+    #[test]
+    fn atom_domains_of_centers() {
+        // The centre of a component of period p has z_p = 0, so it's always in the atom domain of p.
+        let trap = OrbitTrap::default();
+        let centers = [
+            ([0.0, 0.0], 1),
+            ([-1.0, 0.0], 2),
+            ([-0.122_561_166_876_654, 0.744_861_766_619_744], 3),
+            ([-1.754_877_666_246_693, 0.0], 3),
+        ];
+        for &(c, period) in &centers {
+            let pixel = iterate(c, 100, DEFAULT_BAILOUT, &trap, DEFAULT_STRIPE_FREQUENCY);
+            assert_eq!(pixel.atom, period, "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn atom_domain_is_the_smallest_z() {
+        let trap = OrbitTrap::default();
+        let c = [0.3, 0.5];
+        let pixel = iterate(c, 1000, DEFAULT_BAILOUT, &trap, DEFAULT_STRIPE_FREQUENCY);
+
+        let mut z = [0.0f64; 2];
+        let mut norms = Vec::new();
+        for _ in 0..pixel.iters {
+            z = [z[0] * z[0] - z[1] * z[1] + c[0], 2.0 * z[0] * z[1] + c[1]];
+            norms.push((z[0] * z[0] + z[1] * z[1]) as f32);
+        }
+        let smallest = norms[pixel.atom as usize - 1];
+        assert!(norms.iter().all(|&norm| norm >= smallest));
+    }
+
+    #[test]
+    fn misiurewicz_domains() {
+        let trap = OrbitTrap::default();
+        // -2 lands on its fixed point 2 after 2 iterations (0, -2, 2, 2, ...), so z₃ - z₂ = 0.
+        let pixel = iterate(
+            [-2.0, 0.0],
+            100,
+            DEFAULT_BAILOUT,
+            &trap,
+            DEFAULT_STRIPE_FREQUENCY,
+        );
+        assert_eq!(pixel.misiurewicz, 2);
+        // And points near it are in the same domain.
+        let pixel = iterate(
+            [-1.99, 0.001],
+            100,
+            DEFAULT_BAILOUT,
+            &trap,
+            DEFAULT_STRIPE_FREQUENCY,
+        );
+        assert_eq!(pixel.misiurewicz, 2);
+        // The preperiod is never less than 1, since z₁ - z₀ = c isn't counted.
+        let pixel = iterate(
+            [0.0, 0.0],
+            100,
+            DEFAULT_BAILOUT,
+            &trap,
+            DEFAULT_STRIPE_FREQUENCY,
+        );
+        assert!(pixel.misiurewicz >= 1);
+    }
+}
@@ -0,0 +1,58 @@
+//! Turning the linear RGB colours the colourings come up with into what actually gets stored in the output.
+//!
+//! Palettes are defined in sRGB and everything in between (interpolation, lighting, blending layers) happens in linear RGB,
+//! so the only place sRGB comes back in is right at the end. If the swapchain has an `*Srgb` format the GPU encodes it
+//! for us; otherwise `fs_main` has to do it itself, or the image comes out far too dark. Images saved from the CPU are
+//! always 8-bit sRGB.
+//!
+//! With only 8 bits per channel, smooth gradients show visible bands, so the encoded values can be ordered dithered:
+//! each pixel gets nudged by up to half a step either way according to a Bayer matrix before being rounded,
+//! which trades the bands for a fine, even pattern.
+//!
+//! This mirrors the end of `fs_main` in `colorize.wgsl`, so that images rendered on the CPU come out the same.
+
+use wgpu::TextureFormat;
+
+use crate::palette::linear_to_srgb;
+
+/// The size of the Bayer matrix used for ordered dithering (`DITHER_SIZE` in `colorize.wgsl`).
+pub const DITHER_SIZE: u32 = 8;
+
+/// Whether the GPU encodes colours written to `format` as sRGB by itself.
+pub fn is_srgb(format: TextureFormat) -> bool {
+    format.describe().srgb
+}
+
+/// The size of the steps between the values each channel of `format` can hold, from 0 to 1,
+/// or 0 if it's a floating-point format which doesn't need dithering.
+pub fn quantization_step(format: TextureFormat) -> f32 {
+    match format {
+        TextureFormat::Rgb10a2Unorm => 1.0 / 1023.0,
+        TextureFormat::Rgba16Float | TextureFormat::Rgba32Float => 0.0,
+        _ => 1.0 / 255.0,
+    }
+}
+
+/// The threshold for the pixel at `x`, `y` from the Bayer matrix, from -0.5 to 0.5 (`bayer` in `colorize.wgsl`).
+pub fn dither_threshold(x: u32, y: u32) -> f32 {
+    // The bits of the matrix's index are those of x ^ y and y interleaved, with the lowest ones first.
+    let (x, y) = (x % DITHER_SIZE, y % DITHER_SIZE);
+    let xy = x ^ y;
+    let mut index = 0;
+    let mut bit = 1;
+    while bit < DITHER_SIZE {
+        index = (index << 2) | ((((xy & bit) != 0) as u32) << 1) | ((y & bit) != 0) as u32;
+        bit *= 2;
+    }
+    (index as f32 + 0.5) / (DITHER_SIZE * DITHER_SIZE) as f32 - 0.5
+}
+
+/// Encodes the linear RGB `color` of the pixel at `x`, `y` as 8-bit sRGB, dithering it if `dither` is set.
+pub fn encode(color: [f32; 3], x: u32, y: u32, dither: bool) -> [u8; 3] {
+    let offset = if dither {
+        dither_threshold(x, y) / 255.0
+    } else {
+        0.0
+    };
+    linear_to_srgb(color).map(|c| ((c + offset).clamp(0.0, 1.0) * 255.0).round() as u8)
+}
@@ -0,0 +1,670 @@
+//! Gradient palettes for colouring escaped pixels.
+//!
+//! A palette is a list of colour stops, which gets baked into a 1D texture for `colorize.wgsl` to sample.
+//! Where in the palette a pixel lands is `value * scale + offset`, where `value` is what the colouring mode comes up with,
+//! and what happens outside of 0 to 1 is up to `wrap`.
+//!
+//! Palettes can be loaded from and saved to our own text format, Fractint `.map` files and GIMP `.ggr` gradients;
+//! see `Palette::load`.
+
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The number of texels the palette gets baked into.
+pub const TEXTURE_SIZE: u32 = 1024;
+
+/// The number of colours in a Fractint `.map` file.
+const MAP_COLORS: usize = 256;
+/// How many linear segments each pair of stops gets split into when saving to a `.ggr` file,
+/// so that GIMP's RGB blending ends up looking like ours.
+const GGR_SEGMENTS_PER_STOP: usize = 32;
+/// How many pieces each `.ggr` segment gets split into when loading it.
+const GGR_SAMPLES_PER_SEGMENT: usize = 16;
+
+/// A colour at a position along a palette.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stop {
+    /// Where the stop is, from 0 to 1.
+    pub position: f32,
+    /// The colour's sRGB components, from 0 to 1.
+    pub color: [f32; 3],
+}
+
+/// The colour space colours are blended in between stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Blends the physical amount of light, which keeps the brightness between two colours even.
+    LinearRgb,
+    /// Blends in a perceptual colour space, which avoids the muddy greys you get halfway between opposite hues.
+    Oklab,
+}
+
+/// What happens to positions outside of 0 to 1 (the `WRAP_*` constants in `colorize.wgsl`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    /// Use the colour at the nearest end.
+    Clamp = 0,
+    /// Start over from the beginning.
+    Repeat = 1,
+    /// Go back through the palette in reverse every other time, so that it never jumps between its ends.
+    Mirror = 2,
+}
+
+/// Which way a cycling palette moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Colours move towards lower values, as if the palette were sliding outwards from the set.
+    Forward,
+    /// Colours move towards higher values.
+    Backward,
+}
+
+/// The classic palette cycling animation, where the palette's offset advances over time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cycling {
+    /// How many times the palette goes past per second.
+    pub speed: f32,
+    pub direction: Direction,
+}
+
+impl Default for Cycling {
+    fn default() -> Self {
+        Self {
+            speed: 0.1,
+            direction: Direction::Forward,
+        }
+    }
+}
+
+impl Cycling {
+    /// How fast the palette's offset changes, in palette lengths per second.
+    pub fn velocity(&self) -> f32 {
+        match self.direction {
+            Direction::Forward => self.speed,
+            Direction::Backward => -self.speed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    /// The palette's colour stops, sorted by position.
+    pub stops: Vec<Stop>,
+    pub interpolation: Interpolation,
+    pub wrap: Wrap,
+    /// Shifts the palette along, in units of the palette's length.
+    pub offset: f32,
+    /// How many times the palette gets traversed as the colouring value goes from 0 to 1.
+    pub scale: f32,
+}
+
+impl Default for Palette {
+    /// A palette that goes from dark blue to white to orange, and then back to dark blue.
+    fn default() -> Self {
+        Self::new(vec![
+            stop(0.0, [0, 7, 100]),
+            stop(0.16, [32, 107, 203]),
+            stop(0.42, [237, 255, 255]),
+            stop(0.6425, [255, 170, 0]),
+            stop(0.8575, [0, 2, 0]),
+            stop(1.0, [0, 7, 100]),
+        ])
+    }
+}
+
+impl Palette {
+    /// The default palette for pixels in the set: dark purple to pale lilac, and back again.
+    pub fn interior() -> Self {
+        Self::new(vec![
+            stop(0.0, [20, 0, 40]),
+            stop(0.5, [200, 170, 230]),
+            stop(1.0, [20, 0, 40]),
+        ])
+    }
+
+    /// Creates a palette from `stops`, with the default settings.
+    pub fn new(mut stops: Vec<Stop>) -> Self {
+        // A stable sort keeps stops at the same position in order, which is how hard edges are made.
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        Self {
+            stops,
+            interpolation: Interpolation::Oklab,
+            wrap: Wrap::Repeat,
+            offset: 0.0,
+            scale: 1.0,
+        }
+    }
+
+    /// Gets the linear RGB colour at `position`, which has already had `wrap` applied.
+    pub fn sample(&self, position: f32) -> [f32; 3] {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return [0.0; 3],
+        };
+
+        if position <= first.position {
+            return srgb_to_linear(first.color);
+        }
+
+        let next = match self.stops.iter().position(|stop| stop.position > position) {
+            Some(next) => next,
+            None => return srgb_to_linear(last.color),
+        };
+        let (a, b) = (&self.stops[next - 1], &self.stops[next]);
+        let t = (position - a.position) / (b.position - a.position);
+
+        let (a, b) = (srgb_to_linear(a.color), srgb_to_linear(b.color));
+        match self.interpolation {
+            Interpolation::LinearRgb => lerp(a, b, t),
+            Interpolation::Oklab => {
+                oklab_to_linear(lerp(linear_to_oklab(a), linear_to_oklab(b), t))
+            }
+        }
+    }
+
+    /// Gets the linear RGB colour for a colouring value of `value`, the same way `colorize.wgsl` does.
+    pub fn color(&self, value: f32) -> [f32; 3] {
+        let position = value * self.scale + self.offset;
+        let position = match self.wrap {
+            Wrap::Clamp => position.clamp(0.0, 1.0),
+            Wrap::Repeat => position.rem_euclid(1.0),
+            Wrap::Mirror => 1.0 - (position.rem_euclid(2.0) - 1.0).abs(),
+        };
+        self.sample(position)
+    }
+
+    /// Bakes the palette into `TEXTURE_SIZE` sRGB texels with 8 bits per channel, for uploading as a texture.
+    pub fn texels(&self) -> Vec<[u8; 4]> {
+        (0..TEXTURE_SIZE)
+            .map(|i| {
+                // Sample at the center of each texel, which is where the GPU considers it to be.
+                let [r, g, b] = linear_to_srgb(self.sample((i as f32 + 0.5) / TEXTURE_SIZE as f32));
+                [to_u8(r), to_u8(g), to_u8(b), 255]
+            })
+            .collect()
+    }
+
+    /// Loads a palette from `path`, picking the format from its extension:
+    /// `.map` for Fractint maps, `.ggr` for GIMP gradients, and our own text format for anything else.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PaletteError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        match extension(path).as_deref() {
+            Some("map") => Self::from_map(&text),
+            Some("ggr") => Self::from_ggr(&text),
+            _ => Self::from_text(&text),
+        }
+    }
+
+    /// Saves the palette to `path`, picking the format from its extension like `load`.
+    ///
+    /// Fractint maps and GIMP gradients only hold the colours, so the rest of the settings are lost.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), PaletteError> {
+        let path = path.as_ref();
+        let text = match extension(path).as_deref() {
+            Some("map") => self.to_map(),
+            Some("ggr") => self.to_ggr(),
+            _ => self.to_text(),
+        };
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Parses our own palette format.
+    ///
+    /// Each line is either a stop, made up of its position and a `#rrggbb` colour,
+    /// or one of `interpolation`, `wrap`, `offset` or `scale` followed by its value.
+    /// Anything after a `;` is a comment.
+    pub fn from_text(text: &str) -> Result<Self, PaletteError> {
+        let mut palette = Self::new(Vec::new());
+
+        for (number, line) in lines(text) {
+            let error = |message: &str| PaletteError::parse(number, message);
+            let mut words = line.split_whitespace();
+            let (key, value) = match (words.next(), words.next(), words.next()) {
+                (Some(key), Some(value), None) => (key, value),
+                _ => return Err(error("expected a key and a value")),
+            };
+
+            match key {
+                "interpolation" => {
+                    palette.interpolation = match value {
+                        "linear-rgb" => Interpolation::LinearRgb,
+                        "oklab" => Interpolation::Oklab,
+                        _ => return Err(error("unknown interpolation")),
+                    }
+                }
+                "wrap" => {
+                    palette.wrap = match value {
+                        "clamp" => Wrap::Clamp,
+                        "repeat" => Wrap::Repeat,
+                        "mirror" => Wrap::Mirror,
+                        _ => return Err(error("unknown wrap mode")),
+                    }
+                }
+                "offset" => palette.offset = value.parse().map_err(|_| error("invalid offset"))?,
+                "scale" => palette.scale = value.parse().map_err(|_| error("invalid scale"))?,
+                _ => {
+                    let position: f32 = key
+                        .parse()
+                        .map_err(|_| error("expected a setting or a stop position"))?;
+                    let color = parse_hex(value).ok_or_else(|| error("invalid colour"))?;
+                    palette.stops.push(Stop { position, color });
+                }
+            }
+        }
+
+        palette
+            .stops
+            .sort_by(|a, b| a.position.total_cmp(&b.position));
+        Ok(palette)
+    }
+
+    /// Formats the palette in the format `from_text` reads.
+    pub fn to_text(&self) -> String {
+        let interpolation = match self.interpolation {
+            Interpolation::LinearRgb => "linear-rgb",
+            Interpolation::Oklab => "oklab",
+        };
+        let wrap = match self.wrap {
+            Wrap::Clamp => "clamp",
+            Wrap::Repeat => "repeat",
+            Wrap::Mirror => "mirror",
+        };
+
+        let mut text = format!(
+            "interpolation {}\nwrap {}\noffset {}\nscale {}\n",
+            interpolation, wrap, self.offset, self.scale
+        );
+        for stop in &self.stops {
+            let [r, g, b] = stop.color;
+            text += &format!(
+                "{} #{:02x}{:02x}{:02x}\n",
+                stop.position,
+                to_u8(r),
+                to_u8(g),
+                to_u8(b)
+            );
+        }
+        text
+    }
+
+    /// Parses a Fractint `.map` file, which is a list of up to 256 colours with components from 0 to 255.
+    ///
+    /// Fractint cycles through the colours, so they get spread evenly with room left at the end to blend back to the first.
+    pub fn from_map(text: &str) -> Result<Self, PaletteError> {
+        let mut colors = Vec::new();
+        for (number, line) in lines(text) {
+            // Anything after the three components is a comment.
+            let components: Option<Vec<u8>> = line
+                .split_whitespace()
+                .take(3)
+                .map(|component| component.parse().ok())
+                .collect();
+            match components.as_deref() {
+                Some(&[r, g, b]) => colors.push([r, g, b]),
+                _ => return Err(PaletteError::parse(number, "expected three components")),
+            }
+        }
+
+        if colors.is_empty() {
+            return Err(PaletteError::parse(0, "no colours"));
+        }
+
+        let len = colors.len();
+        let mut stops: Vec<_> = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| stop(i as f32 / len as f32, color))
+            .collect();
+        stops.push(stop(1.0, colors[0]));
+
+        let mut palette = Self::new(stops);
+        palette.interpolation = Interpolation::LinearRgb;
+        Ok(palette)
+    }
+
+    /// Formats the palette as a Fractint `.map` file.
+    pub fn to_map(&self) -> String {
+        (0..MAP_COLORS)
+            .map(|i| {
+                let [r, g, b] = linear_to_srgb(self.sample(i as f32 / MAP_COLORS as f32));
+                format!("{} {} {}\n", to_u8(r), to_u8(g), to_u8(b))
+            })
+            .collect()
+    }
+
+    /// Parses a GIMP `.ggr` gradient.
+    ///
+    /// Its segments each have their own blending function and colour model, and even plain blends are done on sRGB values,
+    /// which none of our interpolation modes do, so each segment gets sampled into several stops; the alpha channel is ignored.
+    pub fn from_ggr(text: &str) -> Result<Self, PaletteError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()));
+
+        match lines.next() {
+            Some((_, "GIMP Gradient")) => {}
+            _ => return Err(PaletteError::parse(1, "expected `GIMP Gradient`")),
+        }
+
+        let (number, line) = lines
+            .by_ref()
+            .find(|(_, line)| !line.starts_with("Name:"))
+            .ok_or_else(|| PaletteError::parse(0, "missing segment count"))?;
+        let count: usize = line
+            .parse()
+            .map_err(|_| PaletteError::parse(number, "invalid segment count"))?;
+
+        let mut stops = Vec::new();
+        for _ in 0..count {
+            let (number, line) = lines
+                .next()
+                .ok_or_else(|| PaletteError::parse(0, "missing segments"))?;
+            let segment = GgrSegment::parse(line)
+                .ok_or_else(|| PaletteError::parse(number, "invalid segment"))?;
+
+            for i in 0..=GGR_SAMPLES_PER_SEGMENT {
+                let t = i as f32 / GGR_SAMPLES_PER_SEGMENT as f32;
+                let position = segment.left + t * (segment.right - segment.left);
+                stops.push(Stop {
+                    position,
+                    color: segment.color(position),
+                });
+            }
+        }
+
+        let mut palette = Self::new(stops);
+        palette.interpolation = Interpolation::LinearRgb;
+        palette.wrap = Wrap::Clamp;
+        Ok(palette)
+    }
+
+    /// Formats the palette as a GIMP `.ggr` gradient, made up of linear RGB segments.
+    pub fn to_ggr(&self) -> String {
+        let segments = (self.stops.len().max(2) - 1) * GGR_SEGMENTS_PER_STOP;
+
+        let mut text = format!("GIMP Gradient\nName: gpu-mandelbrot\n{}\n", segments);
+        for i in 0..segments {
+            let left = i as f32 / segments as f32;
+            let right = (i + 1) as f32 / segments as f32;
+            let [lr, lg, lb] = linear_to_srgb(self.sample(left));
+            let [rr, rg, rb] = linear_to_srgb(self.sample(right));
+            text += &format!(
+                "{:.6} {:.6} {:.6} {:.6} {:.6} {:.6} 1.000000 {:.6} {:.6} {:.6} 1.000000 0 0 0 0\n",
+                left,
+                (left + right) / 2.0,
+                right,
+                lr,
+                lg,
+                lb,
+                rr,
+                rg,
+                rb
+            );
+        }
+        text
+    }
+}
+
+/// A segment of a GIMP gradient.
+struct GgrSegment {
+    left: f32,
+    middle: f32,
+    right: f32,
+    left_color: [f32; 3],
+    right_color: [f32; 3],
+    /// How the colours are blended: linear, curved, sine, sphere increasing, sphere decreasing or step.
+    blending: u32,
+    /// Whether the colours are blended in RGB, or counterclockwise or clockwise around the HSV hue circle.
+    coloring: u32,
+}
+
+impl GgrSegment {
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<f32> = line
+            .split_whitespace()
+            .map(|field| field.parse().ok())
+            .collect::<Option<_>>()?;
+        // Newer versions of GIMP add the endpoints' colour types on the end, which we don't support.
+        if fields.len() < 13 {
+            return None;
+        }
+
+        Some(Self {
+            left: fields[0],
+            middle: fields[1],
+            right: fields[2],
+            left_color: [fields[3], fields[4], fields[5]],
+            right_color: [fields[7], fields[8], fields[9]],
+            blending: fields[11] as u32,
+            coloring: fields[12] as u32,
+        })
+    }
+
+    /// Gets the colour at `position`, the way GIMP does.
+    fn color(&self, position: f32) -> [f32; 3] {
+        let len = self.right - self.left;
+        let (position, middle) = if len > 0.0 {
+            (
+                (position - self.left) / len,
+                (self.middle - self.left) / len,
+            )
+        } else {
+            (0.5, 0.5)
+        };
+
+        // The position with the middle point moved to 0.5.
+        let linear = if position <= middle {
+            if middle > 0.0 {
+                0.5 * position / middle
+            } else {
+                0.0
+            }
+        } else if middle < 1.0 {
+            0.5 + 0.5 * (position - middle) / (1.0 - middle)
+        } else {
+            1.0
+        };
+
+        let t = match self.blending {
+            1 => position.powf(0.5f32.ln() / middle.max(f32::EPSILON).ln()),
+            2 => ((PI * linear - PI / 2.0).sin() + 1.0) / 2.0,
+            3 => (1.0 - (linear - 1.0) * (linear - 1.0)).sqrt(),
+            4 => 1.0 - (1.0 - linear * linear).sqrt(),
+            5 => (position >= middle) as u32 as f32,
+            _ => linear,
+        };
+
+        match self.coloring {
+            0 => lerp(self.left_color, self.right_color, t),
+            _ => {
+                let [lh, ls, lv] = rgb_to_hsv(self.left_color);
+                let [rh, rs, rv] = rgb_to_hsv(self.right_color);
+                // Go the long way around the hue circle if the direction says to.
+                let hue_delta = match self.coloring {
+                    1 => (rh - lh).rem_euclid(1.0),
+                    _ => -(lh - rh).rem_euclid(1.0),
+                };
+                let hue = (lh + t * hue_delta).rem_euclid(1.0);
+                hsv_to_rgb([hue, ls + t * (rs - ls), lv + t * (rv - lv)])
+            }
+        }
+    }
+}
+
+/// An error loading or saving a palette.
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(io::Error),
+    /// The file wasn't in the format it was supposed to be in; `line` is 1-based, or 0 if it's about the whole file.
+    Parse {
+        line: usize,
+        message: String,
+    },
+}
+
+impl PaletteError {
+    fn parse(line: usize, message: &str) -> Self {
+        Self::Parse {
+            line,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for PaletteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{}", error),
+            Self::Parse { line: 0, message } => write!(f, "{}", message),
+            Self::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for PaletteError {}
+
+impl From<io::Error> for PaletteError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Makes a stop from 8-bit sRGB components.
+fn stop(position: f32, [r, g, b]: [u8; 3]) -> Stop {
+    Stop {
+        position,
+        color: [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0],
+    }
+}
+
+/// The lowercase extension of `path`, if it has one.
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+}
+
+/// The non-empty lines of `text` with their 1-based line numbers, with `;` comments removed.
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.split(';').next().unwrap_or("").trim()))
+        .filter(|(_, line)| !line.is_empty())
+}
+
+/// Parses a `#rrggbb` colour.
+fn parse_hex(text: &str) -> Option<[f32; 3]> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some(stop(0.0, [(value >> 16) as u8, (value >> 8) as u8, value as u8]).color)
+}
+
+fn to_u8(component: f32) -> u8 {
+    (component.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn lerp(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        a[0] + t * (b[0] - a[0]),
+        a[1] + t * (b[1] - a[1]),
+        a[2] + t * (b[2] - a[2]),
+    ]
+}
+
+/// Converts sRGB components from 0 to 1 to the linear amount of light they represent.
+pub fn srgb_to_linear(color: [f32; 3]) -> [f32; 3] {
+    color.map(|c| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+/// The inverse of `srgb_to_linear`.
+pub fn linear_to_srgb(color: [f32; 3]) -> [f32; 3] {
+    color.map(|c| {
+        if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    })
+}
+
+/// Converts linear sRGB to OKLab, using the matrices from https://bottosson.github.io/posts/oklab/.
+// The constants are kept exactly as published, even though they're more precise than an `f32` can hold.
+#[allow(clippy::excessive_precision)]
+fn linear_to_oklab([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+#[allow(clippy::excessive_precision)]
+fn oklab_to_linear([l, a, b]: [f32; 3]) -> [f32; 3] {
+    let l_ = l + 0.3963377774 * a + 0.2158037573 * b;
+    let m_ = l - 0.1055613458 * a - 0.0638541728 * b;
+    let s_ = l - 0.0894841775 * a - 1.2914855480 * b;
+    let (l, m, s) = (l_ * l_ * l_, m_ * m_ * m_, s_ * s_ * s_);
+
+    [
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    ]
+}
+
+fn rgb_to_hsv([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0) / 6.0
+    } else if max == g {
+        ((b - r) / delta + 2.0) / 6.0
+    } else {
+        ((r - g) / delta + 4.0) / 6.0
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    [hue, saturation, max]
+}
+
+fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let h = h * 6.0;
+    let c = v * s;
+    let x = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let m = v - c;
+    let [r, g, b] = match h as u32 {
+        0 => [c, x, 0.0],
+        1 => [x, c, 0.0],
+        2 => [0.0, c, x],
+        3 => [0.0, x, c],
+        4 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    [r + m, g + m, b + m]
+}
@@ -0,0 +1,123 @@
+//! Rendering using perturbation theory.
+//!
+//! Rather than iterating every pixel at full precision, a single reference orbit is iterated at full precision on the CPU.
+//! Every pixel's orbit is then expressed as a small offset (delta) from the reference orbit, which is small enough that the GPU can iterate it using plain `f32`s:
+//!
+//! ```text
+//! z = Z + δz, c = C + δc
+//! δz' = 2Zδz + δz² + δc
+//! ```
+//!
+//! This breaks down when a pixel's orbit gets too close to zero relative to the reference orbit, since the rounded reference loses the precision needed to represent it.
+//! These 'glitched' pixels are detected using Pauldelbrot's criterion (|Z + δz| < 10⁻³|Z|), and re-rendered using secondary reference orbits picked from within the glitches.
+//! Reading the glitches back means waiting for the GPU, so only one secondary reference is picked per frame,
+//! and the image is rendered again with every one picked so far until the glitches are gone or `MAX_SECONDARY_REFERENCES` is reached.
+//! Any which are still glitched after that are drawn in `GLITCH_COLOR`, so that they can't be mistaken for part of the image.
+//! On the web the GPU's results can't be read back within a frame, so no secondary references are picked and every glitch gets drawn that way.
+//!
+//! Julia sets work the same way, except that every pixel shares the reference's c, so δc = 0 and each pixel's offset from the reference is δz₀ instead.
+
+use bytemuck::Pod;
+use bytemuck::Zeroable;
+
+use crate::num::Complex;
+use crate::series;
+use crate::series::ComplexExp;
+
+/// The maximum number of secondary references to try before giving up on fixing any remaining glitches.
+pub const MAX_SECONDARY_REFERENCES: u32 = 8;
+
+/// The colour glitched pixels are drawn in, in linear RGB (which `pixel_color` in `colorize.wgsl` has to match).
+pub const GLITCH_COLOR: [f32; 3] = [1.0, 0.0, 1.0];
+
+/// The orbit of a single point, iterated at full precision, which pixels' orbits are computed relative to.
+#[derive(Debug, Clone)]
+pub struct ReferenceOrbit {
+    /// The parameter this is an orbit of.
+    pub c: Complex,
+    /// Whether this is an orbit in the Julia set of `c`, starting from a point on the screen rather than from 0.
+    pub julia: bool,
+    /// The values of z at each iteration, starting from z₀, rounded to `f32`s to be sent to the GPU.
+    ///
+    /// If the reference escapes, this stops at the first value outside the escape radius.
+    pub orbit: Vec<[f32; 2]>,
+    /// The same values converted straight from full precision, for series approximation and BLA to be built from.
+    ///
+    /// Their coefficients grow by a factor of |2Z| every iteration, so rounding Z to an `f32` first would throw away most of their precision.
+    pub points: Vec<ComplexExp>,
+}
+
+impl ReferenceOrbit {
+    /// Iterates `c` for up to `iterations` iterations, or until it gets further than `bailout` from the origin, at the precision of `c`.
+    pub fn new(c: Complex, iterations: u32, bailout: f32) -> Self {
+        Self::iterate(Complex::default(), c, false, iterations, bailout)
+    }
+
+    /// Iterates `z0` in the Julia set of `c`, like `new`.
+    pub fn julia(z0: Complex, c: Complex, iterations: u32, bailout: f32) -> Self {
+        Self::iterate(z0, c, true, iterations, bailout)
+    }
+
+    fn iterate(z0: Complex, c: Complex, julia: bool, iterations: u32, bailout: f32) -> Self {
+        let mut orbit = Vec::with_capacity(iterations as usize + 1);
+        let mut points = Vec::with_capacity(iterations as usize + 1);
+        orbit.push(z0.to_f32());
+        points.push(series::from_complex(&z0));
+
+        let mut z = z0;
+        for _ in 0..iterations {
+            z = z.square() + &c;
+
+            let [real, imag] = z.to_f32();
+            orbit.push([real, imag]);
+            points.push(series::from_complex(&z));
+
+            // This also stops the integer portion of `z` from overflowing, as long as the bailout isn't too big.
+            if real * real + imag * imag >= bailout * bailout {
+                break;
+            }
+        }
+
+        Self {
+            c,
+            julia,
+            orbit,
+            points,
+        }
+    }
+
+    /// Whether this orbit escaped before reaching `iterations` iterations,
+    /// in which case pixels which outlast it need a different reference.
+    pub fn escaped(&self, iterations: u32) -> bool {
+        self.orbit.len() <= iterations as usize
+    }
+}
+
+/// A summary of the glitched pixels written by the GPU after each pass.
+#[derive(Clone, Copy, Zeroable, Pod, Debug)]
+#[repr(C)]
+pub struct Glitches {
+    /// The number of glitched pixels.
+    pub count: u32,
+    /// The most severely glitched pixel, which makes a good secondary reference, since it's near the centre of its glitch.
+    ///
+    /// The top 8 bits rank how badly it's glitched (lower is worse), and the bottom 24 bits are the pixel's index.
+    pub worst: u32,
+}
+
+impl Glitches {
+    /// The value the summary needs to be reset to before each pass.
+    pub const EMPTY: Self = Self {
+        count: 0,
+        worst: u32::MAX,
+    };
+
+    /// The index of the pixel which should be used as the next reference, if there are any glitches.
+    pub fn worst_pixel(&self) -> Option<u32> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.worst & 0x00ffffff)
+        }
+    }
+}
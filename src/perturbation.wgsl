@@ -0,0 +1,275 @@
+// Iterates every pixel relative to a reference orbit computed on the CPU; see `perturbation.rs` for the details.
+
+[[block]]
+struct Orbit {
+    points: [[stride(8)]] array<vec2<f32>>;
+};
+
+[[block]]
+struct Glitches {
+    count: atomic<u32>;
+    // The most severely glitched pixel; the top 8 bits rank how glitched it is (lower is worse), and the bottom 24 are its index.
+    worst: atomic<u32>;
+};
+
+// A linear approximation of some number of iterations: δz' = Aδz + Bδc (see `bla.rs`).
+struct Bla {
+    a: vec2<f32>;
+    b: vec2<f32>;
+    a_exponent: i32;
+    b_exponent: i32;
+    // The largest |δz| this is accurate for.
+    radius: f32;
+    radius_exponent: i32;
+};
+
+[[block]]
+struct BlaTable {
+    // Every level of the table one after the other, starting with the single steps.
+    entries: [[stride(32)]] array<Bla>;
+};
+
+[[group(0), binding(1)]] var<storage, read_write> pixels: Pixels;
+[[group(0), binding(2)]] var<storage, read> orbit: Orbit;
+[[group(0), binding(3)]] var<storage, read_write> glitches: Glitches;
+[[group(0), binding(5)]] var<storage, read> bla: BlaTable;
+
+// The smallest exponent a delta can have while still having full precision as an f32 (whose smallest normal exponent is -126).
+let min_exponent: i32 = -100;
+
+// Pauldelbrot's criterion: a pixel is glitched once |Z + δz| < 10^-3 |Z|, which squared is 10^-6.
+let glitch_tolerance: f32 = 0.000001;
+
+// The range |dz/dc|'s mantissa is allowed to drift out to before it gets normalized.
+// It's only normalized occasionally since that's slow, and it can grow by at most 2 × the bailout each iteration without overflowing.
+let max_derivative: f32 = 10000000000000000000.0;
+let min_derivative: f32 = 0.0000000000000000001;
+
+// Normalizes dz/dc, making sure it has an exponent the pixel size can be added to its mantissa at even if it's 0.
+fn normalize_derivative(derivative: ComplexExp, pixel_size: FloatExp) -> ComplexExp {
+    let normalized = complex_exp_normalize(derivative);
+    if (normalized.exponent == ZERO_EXPONENT) {
+        return ComplexExp(vec2<f32>(0.0, 0.0), pixel_size.exponent);
+    }
+    return normalized;
+}
+
+// Gets the pixel size relative to `derivative`'s exponent, which is what gets added to its mantissa each iteration.
+// For a Julia set the derivative is dz/dz₀ instead, which doesn't have anything added.
+fn derivative_step(derivative: ComplexExp, pixel_size: FloatExp) -> f32 {
+    if (settings.julia != 0u) {
+        return 0.0;
+    }
+    return float_exp_to_f32(FloatExp(pixel_size.mantissa, pixel_size.exponent - derivative.exponent));
+}
+
+// Records a glitched pixel, with `severity` being |Z + δz|² / |Z|² (smaller is worse).
+fn record_glitch(index: u32, severity: f32) {
+    // Atomics can't be called as statements, so the results have to go somewhere.
+    let count = atomicAdd(&glitches.count, 1u);
+
+    // Rank glitches by how many powers of two they're below 1, so that the worst has the lowest key.
+    let rank = 255u - u32(clamp(-log2(severity), 0.0, 255.0));
+    let key = (rank << 24u) | (index % 16777216u);
+    let worst = atomicMin(&glitches.worst, key);
+}
+
+// Finds the longest BLA step which can be taken from iteration `iters` by a pixel with |δz|² = `dz_norm`.
+// Returns its index in the table and the number of iterations it skips, which is 0 if there isn't one.
+// This has to match `BlaTable::lookup` in `bla.rs`.
+fn bla_lookup(iters: u32, dz_norm: FloatExp) -> vec2<u32> {
+    var found = vec2<u32>(0u, 0u);
+    if (iters == 0u) {
+        return found;
+    }
+
+    // The first level has a step for every iteration from 1 until the second last value of the orbit,
+    // and each level after that has half as many.
+    var offset = 0u;
+    var count = settings.reference_len - 2u;
+    let start = iters - 1u;
+    for (var level = 0u; level < settings.bla_levels; level = level + 1u) {
+        let len = 1u << level;
+        if (start % len != 0u || start / len >= count || iters + len > settings.iterations) {
+            break;
+        }
+
+        // Longer steps never have larger radii, so there's no point looking any further once one's out of range.
+        let index = offset + start / len;
+        let radius = FloatExp(bla.entries[index].radius, bla.entries[index].radius_exponent);
+        if (!float_exp_less(dz_norm, float_exp_mul(radius, radius))) {
+            break;
+        }
+
+        found = vec2<u32>(index, len);
+        offset = offset + count;
+        count = count / 2u;
+    }
+
+    return found;
+}
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= settings.width || id.y >= settings.height) {
+        return;
+    }
+
+    let index = id.y * settings.width + id.x;
+    let old_flags = pixels.pixels[index].flags;
+    if (skip_sample(old_flags) || (settings.secondary != 0u && (old_flags & GLITCHED) == 0u)) {
+        // This pixel either isn't being supersampled, or was already rendered correctly by an earlier reference.
+        return;
+    }
+
+    let pixel_size = FloatExp(settings.pixel_size, settings.pixel_size_exponent);
+
+    // z is only known to f32 precision here, which can't tell apart values as close together as the pixels are,
+    // so periodicity checking isn't reliable, but the known components are still worth skipping.
+    let c = settings.camera + pixel_offset(id.xy) * float_exp_to_f32(pixel_size);
+    let period = known_period(c);
+    if (period != 0u) {
+        let pixel = Pixel(settings.iterations, sample_flags(), period, 0.0, vec2<f32>(0.0, 0.0), 0.0, orbit_stats_new().trap, 0.0, 0.0, vec2<f32>(0.0, 0.0), 0u, 0u);
+        pixels.pixels[index] = add_cycle(pixel, c, vec2<f32>(0.0, 0.0), float_exp_to_f32(pixel_size));
+        return;
+    }
+
+    // The offset of this pixel from the reference in pixels.
+    let pixel_dc = pixel_offset(id.xy) - settings.reference_offset;
+
+    var dc_exp = complex_mul_float_exp(pixel_dc, pixel_size);
+    var dc = complex_exp_to_complex(dc_exp);
+
+    // Skip ahead using series approximation (see `series.rs`): δz = a dc + b dc² + c dc³, with dc measured in pixels.
+    let dc2 = complex_mul(pixel_dc, pixel_dc);
+    var dz_exp = complex_exp_add(
+        complex_exp_add(
+            complex_exp_mul_complex(ComplexExp(settings.series_a, settings.series_a_exponent), pixel_dc),
+            complex_exp_mul_complex(ComplexExp(settings.series_b, settings.series_b_exponent), dc2),
+        ),
+        complex_exp_mul_complex(ComplexExp(settings.series_c, settings.series_c_exponent), complex_mul(dc2, pixel_dc)),
+    );
+    var dz = complex_exp_to_complex(dz_exp);
+
+    // dz/dc measured in pixels, for estimating the distance to the set, which starts off as the series' derivative: a + 2b dc + 3c dc².
+    // It gets far too large for an f32 at deep zooms, so it has an exponent, but the mantissa isn't kept normalized.
+    var derivative = normalize_derivative(
+        complex_exp_add(
+            complex_exp_add(
+                ComplexExp(settings.series_a, settings.series_a_exponent),
+                complex_exp_mul_complex(ComplexExp(settings.series_b, settings.series_b_exponent), 2.0 * pixel_dc),
+            ),
+            complex_exp_mul_complex(ComplexExp(settings.series_c, settings.series_c_exponent), 3.0 * dc2),
+        ),
+        pixel_size,
+    );
+    var step_size = derivative_step(derivative, pixel_size);
+
+    // At deep enough zooms δz and δc are too small to fit in an f32, so δz has to be iterated with an extended exponent until it grows large enough.
+    // After that, δc is too small relative to δz to make a difference, so it doesn't matter that it underflows.
+    var extended = max(dz_exp.exponent, dc_exp.exponent) < min_exponent;
+
+    if (settings.julia != 0u) {
+        // For a Julia set every pixel has the same c as the reference, and its offset is δz₀ instead,
+        // which the series approximation already started δz off from.
+        dc_exp = ComplexExp(vec2<f32>(0.0, 0.0), ZERO_EXPONENT);
+        dc = vec2<f32>(0.0, 0.0);
+    }
+
+    // Series approximation and BLA are turned off while these are being used, so this sees every iteration.
+    var stats = orbit_stats_new();
+    var iters = settings.series_skip;
+    var flags = sample_flags();
+    var z = vec2<f32>(0.0, 0.0);
+    var len2 = 0.0;
+    loop {
+        if (iters >= settings.iterations) {
+            break;
+        }
+
+        if (iters + 1u >= settings.reference_len) {
+            // The reference escaped before this pixel did, so there's nothing left to perturb it from.
+            flags = flags | GLITCHED;
+            record_glitch(index, 1.0);
+            break;
+        }
+
+        if (!extended) {
+            dz_exp = complex_exp_normalize(ComplexExp(dz, 0));
+        }
+
+        let step = bla_lookup(iters, complex_exp_norm_sqr(dz_exp));
+        if (step.y != 0u) {
+            // Skip ahead with δz' = Aδz + Bδc, which needs an extended exponent since A and B can be huge.
+            let entry = bla.entries[step.x];
+            // Which means (dz/dc)' = A dz/dc + B.
+            derivative = normalize_derivative(
+                complex_exp_add(
+                    complex_exp_mul(ComplexExp(entry.a, entry.a_exponent), derivative),
+                    complex_exp_mul(ComplexExp(entry.b, entry.b_exponent), ComplexExp(vec2<f32>(pixel_size.mantissa, 0.0), pixel_size.exponent)),
+                ),
+                pixel_size,
+            );
+            step_size = derivative_step(derivative, pixel_size);
+            dz_exp = complex_exp_add(
+                complex_exp_mul(ComplexExp(entry.a, entry.a_exponent), dz_exp),
+                complex_exp_mul(ComplexExp(entry.b, entry.b_exponent), dc_exp),
+            );
+            extended = dz_exp.exponent < min_exponent;
+            dz = complex_exp_to_complex(dz_exp);
+            iters = iters + step.y;
+        } else {
+            // (dz/dc)' = 2z dz/dc + 1, where z = Z + δz.
+            derivative.mantissa = 2.0 * complex_mul(orbit.points[iters] + dz, derivative.mantissa) + vec2<f32>(step_size, 0.0);
+
+            if (extended) {
+                // δz² is far too small to matter here, so δz' = 2Zδz + δc.
+                dz_exp = complex_exp_add(complex_exp_mul_complex(dz_exp, 2.0 * orbit.points[iters]), dc_exp);
+                if (dz_exp.exponent >= min_exponent) {
+                    extended = false;
+                    dz = complex_exp_to_complex(dz_exp);
+                }
+            } else {
+                // δz' = 2Zδz + δz² + δc = (2Z + δz)δz + δc
+                dz = complex_mul(2.0 * orbit.points[iters] + dz, dz) + dc;
+            }
+            iters = iters + 1u;
+        }
+
+        let derivative_size = max(abs(derivative.mantissa.x), abs(derivative.mantissa.y));
+        if (derivative_size > max_derivative || derivative_size < min_derivative) {
+            derivative = normalize_derivative(derivative, pixel_size);
+            step_size = derivative_step(derivative, pixel_size);
+        }
+
+        // While δz is extended it's negligible next to Z, so it's fine for it to still be 0 here.
+        let reference = orbit.points[iters];
+        z = reference + dz;
+        stats = orbit_stats_add(stats, z, iters);
+        len2 = dot(z, z);
+        if (len2 >= settings.bailout * settings.bailout) {
+            break;
+        }
+
+        let reference_len2 = dot(reference, reference);
+        if (len2 < glitch_tolerance * reference_len2) {
+            flags = flags | GLITCHED;
+            record_glitch(index, len2 / reference_len2);
+            break;
+        }
+    }
+
+    var slope = vec2<f32>(0.0, 0.0);
+    var distance = 0.0;
+    var stripe = 0.0;
+    var angle = 0.0;
+    if (iters < settings.iterations) {
+        derivative = normalize_derivative(derivative, pixel_size);
+        slope = smooth_slope(z, derivative.mantissa, derivative.exponent);
+        distance = distance_estimate(len2, length(derivative.mantissa)) * exp2(-f32(derivative.exponent));
+        stripe = stripe_average(stats, iters, len2);
+        angle = atan2(z.y, z.x);
+    }
+
+    pixels.pixels[index] = Pixel(iters, flags, 0u, len2, slope, distance, stats.trap, stripe, angle, vec2<f32>(0.0, 0.0), stats.atom, stats.misiurewicz);
+}
@@ -0,0 +1,177 @@
+//! Choosing which arithmetic to iterate pixels with.
+//!
+//! Cheaper arithmetic is much faster, but it can only tell apart points on the complex plane down to a certain spacing,
+//! after which adjacent pixels collapse into the same value and the image pixelates.
+
+use crate::num::FloatExp;
+
+/// Extra bits of precision needed on top of what it takes to tell apart adjacent pixels,
+/// since rounding errors get amplified as they're iterated.
+const ITERATION_BITS: i64 = 6;
+
+/// How many times the resolution of z it can differ from an earlier value by and still count as periodic.
+const PERIOD_TOLERANCE_FACTOR: f64 = 4.0;
+
+/// The smallest exponent perturbation's deltas can have.
+///
+/// The GPU stores them as `f32` mantissas with `i32` exponents, which get doubled when they're squared,
+/// and the result has to stay clear of `ZERO_EXPONENT` (-10⁹) in `floatexp.wgsl`, which is reserved for 0.
+const MIN_DELTA_EXPONENT: i64 = -(1 << 28);
+
+/// The fewest limbs fixed point is used with, since one limb only resolves 2⁻³², which double-single already beats.
+const MIN_FIXED_POINT_LIMBS: u32 = 2;
+
+/// The most limbs fixed point is used with; any deeper than this and perturbation is much faster.
+pub const MAX_FIXED_POINT_LIMBS: u32 = 2;
+
+/// The arithmetic used to iterate pixels, from cheapest to most precise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Precision {
+    /// Plain `f32`s.
+    Single,
+    /// Double-single arithmetic: pairs of `f32`s, with about twice the precision (see `DoubleSingle`).
+    DoubleSingle,
+    /// Fixed-point numbers with `limbs` 32-bit limbs after the binary point, like `Component`.
+    ///
+    /// These are exact, but the cost of multiplying them goes up with the square of `limbs`.
+    FixedPoint { limbs: u32 },
+    /// Perturbation from a full-precision reference orbit (see the `perturbation` module),
+    /// which works until the pixels' deltas get too small for the GPU's extended exponents.
+    Perturbation,
+}
+
+impl Precision {
+    /// Picks the cheapest arithmetic which can tell apart pixels `pixel_size` apart,
+    /// when no point on the screen is further than `radius` from the origin.
+    pub fn choose(pixel_size: FloatExp, radius: FloatExp) -> Self {
+        let spacing = spacing(pixel_size);
+
+        let bits = (-spacing.log2()).ceil().max(0.0) as u32;
+        let limbs = bits.div_ceil(32).max(MIN_FIXED_POINT_LIMBS);
+
+        [Self::Single, Self::DoubleSingle, Self::FixedPoint { limbs }]
+            .iter()
+            .copied()
+            .filter(|precision| match *precision {
+                Self::FixedPoint { limbs } => limbs <= MAX_FIXED_POINT_LIMBS,
+                _ => true,
+            })
+            .find(|precision| precision.resolves(spacing, radius))
+            .unwrap_or(Self::Perturbation)
+    }
+
+    /// The smallest spacing between points which this arithmetic can tell apart,
+    /// when no point is further than `radius` from the origin.
+    pub fn resolution(self, radius: FloatExp) -> FloatExp {
+        // Floats' precision is relative to the size of the number, whereas fixed point's is absolute.
+        // `radius` is less than 2^exponent, so that's the biggest power of two floats need to be able to represent.
+        // Perturbation's deltas are floats too, but with an extended exponent, so only their smallest exponent limits them.
+        let exponent = match self {
+            Self::Single => radius.exponent() - f32::MANTISSA_DIGITS as i64,
+            Self::DoubleSingle => radius.exponent() - 2 * f32::MANTISSA_DIGITS as i64,
+            Self::FixedPoint { limbs } => -32 * limbs as i64,
+            Self::Perturbation => MIN_DELTA_EXPONENT + f32::MANTISSA_DIGITS as i64,
+        };
+        FloatExp::new(1.0, exponent)
+    }
+
+    /// How close z has to come to an earlier value for a pixel's orbit to count as periodic, or `None` if this arithmetic can't check reliably.
+    ///
+    /// This is a few times the resolution of z (which is always less than 2 before it escapes), to allow for rounding errors.
+    /// That's still well below the spacing between pixels, since the arithmetic is picked to have some precision to spare.
+    pub fn period_tolerance(self) -> Option<FloatExp> {
+        match self {
+            // Perturbation iterates z as an `f32` relative to the reference, which isn't nearly precise enough to compare.
+            Self::Perturbation => None,
+            _ => {
+                Some(self.resolution(FloatExp::from(2.0)) * FloatExp::from(PERIOD_TOLERANCE_FACTOR))
+            }
+        }
+    }
+
+    /// Whether this arithmetic can tell apart points `spacing` apart, when no point is further than `radius` from the origin.
+    pub fn resolves(self, spacing: FloatExp, radius: FloatExp) -> bool {
+        self.resolution(radius) <= spacing
+    }
+
+    /// The smallest pixels can get before this arithmetic can't tell them apart any more, where `radius` is as in `resolution`.
+    pub fn min_pixel_size(self, radius: FloatExp) -> FloatExp {
+        self.resolution(radius) * FloatExp::new(1.0, ITERATION_BITS)
+    }
+}
+
+/// The spacing an arithmetic has to be able to resolve to iterate pixels `pixel_size` apart without them visibly pixelating,
+/// which leaves some room for rounding errors to build up.
+pub fn spacing(pixel_size: FloatExp) -> FloatExp {
+    pixel_size * FloatExp::new(1.0, -ITERATION_BITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chosen_precision_resolves_its_spacing() {
+        let radius = FloatExp::from(2.0);
+        for exponent in (-200..0).step_by(7) {
+            let pixel_size = FloatExp::new(1.0, exponent);
+            let precision = Precision::choose(pixel_size, radius);
+            assert!(
+                precision.resolves(spacing(pixel_size), radius),
+                "{:?} can't resolve pixels 2^{} apart",
+                precision,
+                exponent
+            );
+        }
+    }
+
+    #[test]
+    fn tiers_get_more_precise() {
+        let radius = FloatExp::from(2.0);
+        assert_eq!(
+            Precision::choose(FloatExp::from(0.01), radius),
+            Precision::Single
+        );
+        assert_eq!(
+            Precision::choose(FloatExp::from(1e-8), radius),
+            Precision::DoubleSingle
+        );
+        assert_eq!(
+            Precision::choose(FloatExp::from(1e-16), radius),
+            Precision::FixedPoint { limbs: 2 }
+        );
+        assert_eq!(
+            Precision::choose(FloatExp::from(1e-30), radius),
+            Precision::Perturbation
+        );
+    }
+
+    #[test]
+    fn min_pixel_size_is_the_limit() {
+        let precisions = [
+            Precision::Single,
+            Precision::DoubleSingle,
+            Precision::FixedPoint { limbs: 2 },
+            Precision::Perturbation,
+        ];
+        for precision in precisions {
+            for radius in [FloatExp::from(0.1), FloatExp::from(2.0)] {
+                let pixel_size = precision.min_pixel_size(radius);
+                assert!(precision.resolves(spacing(pixel_size), radius));
+                let smaller = pixel_size * FloatExp::from(0.5);
+                assert!(!precision.resolves(spacing(smaller), radius));
+            }
+        }
+    }
+
+    #[test]
+    fn perturbation_has_a_limit() {
+        let radius = FloatExp::from(2.0);
+        let pixel_size = FloatExp::new(1.0, MIN_DELTA_EXPONENT);
+        assert_eq!(
+            Precision::choose(pixel_size, radius),
+            Precision::Perturbation
+        );
+        assert!(!Precision::Perturbation.resolves(spacing(pixel_size), radius));
+    }
+}
@@ -0,0 +1,205 @@
+//! Series approximation, for skipping the start of every pixel's iteration.
+//!
+//! Close to the start of the orbit, each pixel's delta from the reference is well approximated by a polynomial in δc:
+//!
+//! ```text
+//! δzₙ ≈ Aₙδc + Bₙδc² + Cₙδc³
+//! Aₙ₊₁ = 2ZₙAₙ + 1
+//! Bₙ₊₁ = 2ZₙBₙ + Aₙ²
+//! Cₙ₊₁ = 2ZₙCₙ + 2AₙBₙ
+//! ```
+//!
+//! The coefficients only depend on the reference orbit, so they can be iterated once on the CPU until the approximation stops being accurate,
+//! and then every pixel can start iterating from there rather than from zero.
+//!
+//! For a Julia set the pixels' offsets are δz₀ rather than δc, so the same polynomial works in δz₀ instead,
+//! except that A₀ = 1 rather than 0 and nothing gets added to it each iteration.
+//!
+//! At deep zooms δc is tiny and the coefficients are huge, so δc is measured in pixels rather than on the complex plane,
+//! which means the coefficients are scaled by powers of the pixel size: `a = Aδ`, `b = Bδ²`, `c = Cδ³`.
+//! Even then they can easily be too small for an `f64`, so they're computed using `FloatExp`s.
+
+use crate::num::Complex;
+use crate::num::FloatExp;
+use crate::perturbation::ReferenceOrbit;
+
+/// A complex number made of `FloatExp`s.
+pub type ComplexExp = [FloatExp; 2];
+
+/// How small the cubic term has to be relative to the linear term for the approximation to be trusted.
+///
+/// This is around the precision of an `f32`, so that the terms being left out are smaller than the rounding error the GPU will introduce anyway.
+const TOLERANCE: f64 = 1.0 / (1 << f32::MANTISSA_DIGITS) as f64;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SeriesApproximation {
+    /// The number of iterations which can be skipped.
+    pub skip: u32,
+    /// The scaled coefficients `a`, `b` and `c` at iteration `skip`.
+    pub coefficients: [ComplexExp; 3],
+}
+
+impl SeriesApproximation {
+    /// Finds how far into `reference` the approximation is accurate for every pixel within `radius` pixels of the reference,
+    /// where pixels are `pixel_size` apart on the complex plane.
+    pub fn new(
+        reference: &ReferenceOrbit,
+        iterations: u32,
+        radius: f64,
+        pixel_size: FloatExp,
+    ) -> Self {
+        let zero = FloatExp::default();
+        let (mut a, a_offset) = if reference.julia {
+            ([pixel_size, zero], [zero; 2])
+        } else {
+            ([zero; 2], [pixel_size, zero])
+        };
+        let mut b = [zero; 2];
+        let mut c = [zero; 2];
+
+        let mut out = Self {
+            skip: 0,
+            coefficients: [a, b, c],
+        };
+
+        // Compare squared magnitudes so that there's no need to take square roots.
+        let radius2 = FloatExp::from(radius * radius);
+        let tolerance2 = FloatExp::from(TOLERANCE * TOLERANCE);
+
+        // The GPU needs at least one more value of the reference orbit after the last skipped iteration.
+        let last = (iterations as usize).min(reference.points.len().saturating_sub(2));
+        for (n, z) in reference.points[..last].iter().copied().enumerate() {
+            let z2 = add(z, z);
+
+            let next_a = add(mul(z2, a), a_offset);
+            let next_b = add(mul(z2, b), mul(a, a));
+            let next_c = add(mul(z2, c), mul(add(a, a), b));
+
+            // The approximation's only accurate while the terms it leaves out are negligible,
+            // which we approximate by the cubic term being negligible next to the linear one.
+            if norm_sqr(next_c) * radius2 * radius2 > tolerance2 * norm_sqr(next_a) {
+                break;
+            }
+
+            // If any pixel might have escaped by now, it needs to be iterated for real to find out when.
+            let spread = (norm_sqr(next_a) * radius2).sqrt().to_f64();
+            if norm_sqr(reference.points[n + 1]).sqrt().to_f64() + spread > 2.0 {
+                break;
+            }
+
+            a = next_a;
+            b = next_b;
+            c = next_c;
+
+            out = Self {
+                skip: n as u32 + 1,
+                coefficients: [a, b, c],
+            };
+        }
+
+        out
+    }
+
+    /// Evaluates the approximation for a pixel `dc` pixels away from the reference, giving its δz at iteration `skip`.
+    pub fn evaluate(&self, dc: [f32; 2]) -> ComplexExp {
+        let [a, b, c] = self.coefficients;
+        let dc = [(dc[0] as f64).into(), (dc[1] as f64).into()];
+
+        // Horner's method: ((c dc + b) dc + a) dc
+        mul(add(mul(add(mul(c, dc), b), dc), a), dc)
+    }
+
+    /// Converts the coefficients to what the GPU expects: pairs of `f32` mantissas which share an exponent.
+    pub fn gpu_coefficients(&self) -> ([[f32; 2]; 3], [i32; 3]) {
+        let mut mantissas = [[0.0; 2]; 3];
+        let mut exponents = [0; 3];
+        for (i, coefficient) in self.coefficients.iter().copied().enumerate() {
+            let (mantissa, exponent) = to_gpu(coefficient);
+            mantissas[i] = mantissa;
+            exponents[i] = exponent;
+        }
+        (mantissas, exponents)
+    }
+}
+
+/// Converts a `ComplexExp` to what the GPU's `ComplexExp` expects: a pair of `f32` mantissas which share an exponent.
+pub(crate) fn to_gpu([real, imag]: ComplexExp) -> ([f32; 2], i32) {
+    let exponent = real.exponent().max(imag.exponent());
+    let scale = FloatExp::new(1.0, -exponent);
+    let mantissa = [
+        (real * scale).to_f64() as f32,
+        (imag * scale).to_f64() as f32,
+    ];
+    (mantissa, exponent as i32)
+}
+
+/// Converts a full-precision `Complex` to a `ComplexExp`, keeping as much of its precision as an `f64` mantissa can hold.
+pub(crate) fn from_complex(z: &Complex) -> ComplexExp {
+    [FloatExp::from(&z.real), FloatExp::from(&z.imag)]
+}
+
+pub(crate) fn add(a: ComplexExp, b: ComplexExp) -> ComplexExp {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+pub(crate) fn mul(a: ComplexExp, b: ComplexExp) -> ComplexExp {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+pub(crate) fn norm_sqr(a: ComplexExp) -> FloatExp {
+    a[0] * a[0] + a[1] * a[1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Iterates δz for a pixel `dc` away from `reference` on the complex plane for `iterations` iterations, without any approximation.
+    fn perturb(reference: &ReferenceOrbit, dc: ComplexExp, iterations: u32) -> ComplexExp {
+        let mut dz = [FloatExp::default(); 2];
+        for z in &reference.points[..iterations as usize] {
+            // δz' = (2Z + δz)δz + δc
+            dz = add(mul(add(add(*z, *z), dz), dz), dc);
+        }
+        dz
+    }
+
+    #[test]
+    fn matches_direct_perturbation() {
+        let c = Complex::from([-0.743_643_887_037_158_7, 0.131_825_904_205_311_9]);
+        let reference = ReferenceOrbit::new(c, 2000, 2.0);
+        let pixel_size = FloatExp::from(1e-12);
+        let radius = 600.0;
+        let series = SeriesApproximation::new(&reference, 2000, radius, pixel_size);
+        assert!(series.skip > 10, "only skipped {} iterations", series.skip);
+
+        for &pixel in &[[1.0, 0.0], [-250.0, 310.0], [420.0, 420.0], [0.0, -600.0]] {
+            let dc = [
+                FloatExp::from(pixel[0] as f64) * pixel_size,
+                FloatExp::from(pixel[1] as f64) * pixel_size,
+            ];
+            let expected = perturb(&reference, dc, series.skip);
+            let error = norm_sqr(add(series.evaluate(pixel), [-expected[0], -expected[1]])).sqrt();
+            let tolerance = FloatExp::from(1e-6) * norm_sqr(expected).sqrt();
+            assert!(
+                error < tolerance,
+                "{:?} was off by {:?} after {} iterations",
+                pixel,
+                error,
+                series.skip
+            );
+        }
+    }
+
+    #[test]
+    fn julia_starts_from_offset() {
+        let reference =
+            ReferenceOrbit::julia(Complex::default(), Complex::from([-0.8, 0.156]), 100, 2.0);
+        let series = SeriesApproximation::new(&reference, 0, 0.0, FloatExp::from(0.01));
+        assert_eq!(series.skip, 0);
+        // With nothing skipped, δz is just the pixel's δz₀.
+        let dz = series.evaluate([3.0, -4.0]);
+        assert!((dz[0].to_f64() - 0.03).abs() < 1e-9);
+        assert!((dz[1].to_f64() + 0.04).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,25 @@
+// Counts how many pixels hit the iteration limit or escaped close to it (see `iterations.rs`).
+
+[[block]]
+struct IterationStats {
+    limited: atomic<u32>;
+    near_limit: atomic<u32>;
+};
+
+[[group(0), binding(1)]] var<storage, read_write> pixels: Pixels;
+[[group(0), binding(6)]] var<storage, read_write> stats: IterationStats;
+
+[[stage(compute), workgroup_size(8, 8)]]
+fn main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= settings.width || id.y >= settings.height) {
+        return;
+    }
+
+    let iters = pixels.pixels[id.y * settings.width + id.x].iters;
+    // Atomics can't be called as statements, so the results have to go somewhere.
+    if (iters >= settings.iterations) {
+        let limited = atomicAdd(&stats.limited, 1u);
+    } elseif (iters >= settings.near_limit) {
+        let near_limit = atomicAdd(&stats.near_limit, 1u);
+    }
+}
@@ -0,0 +1,191 @@
+//! Supersampling: iterating several points within each pixel and averaging their colours,
+//! to get rid of the aliasing along the edge of the set and its filaments.
+//!
+//! Each sample is a whole pass of iterating and colouring every pixel, offset from the pixels' centres by the same amount.
+//! The colours get added up in linear RGB in the accumulation buffer, and `fs_main` divides them out at the end.
+//!
+//! The adaptive mode first iterates just the centre of each pixel, and then only supersamples the pixels whose iteration
+//! counts are far enough from their neighbours' (see `edges.wgsl`), since that's where the aliasing is.
+//!
+//! While the view stays still, each frame adds another sample to every pixel (or just the edges, if it's adaptive)
+//! at a different offset from `temporal_offset`, so the image keeps getting smoother the longer it's looked at,
+//! up to `MAX_ACCUMULATED_FRAMES`.
+//!
+//! The pixels of each of the first frame's passes are kept, so that when only the colouring changes (like while the palette's cycling),
+//! they can be coloured again without iterating anything; the samples added by later frames aren't, since there can be so many of them.
+
+use std::iter;
+
+/// How many iterations apart a pixel's neighbours have to be for adaptive supersampling to count it as an edge.
+pub const DEFAULT_EDGE_THRESHOLD: u32 = 4;
+
+/// How many frames samples keep getting accumulated over while the view stays still, after which the image is left as is.
+pub const MAX_ACCUMULATED_FRAMES: u32 = 256;
+
+/// Where the samples go within each pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// An n×n grid of evenly spaced samples.
+    Grid(u32),
+    /// An n×n grid with each sample moved somewhere random within its cell,
+    /// which turns the aliasing left over from the grid's regularity into noise.
+    Jittered(u32),
+    /// Four samples on a grid rotated so that no two share a row or column,
+    /// which catches nearly horizontal and vertical edges far better than a 2×2 grid.
+    RotatedGrid,
+}
+
+impl Pattern {
+    /// The samples' offsets from the centre of the pixel, in pixels.
+    pub fn offsets(self) -> Vec<[f32; 2]> {
+        match self {
+            Self::Grid(size) => grid(size, |_, _| [0.5, 0.5]),
+            Self::Jittered(size) => grid(size, |x, y| {
+                let seed = y * size + x;
+                [hash(2 * seed), hash(2 * seed + 1)]
+            }),
+            Self::RotatedGrid => vec![
+                [0.125, 0.375],
+                [0.375, -0.125],
+                [-0.125, -0.375],
+                [-0.375, 0.125],
+            ],
+        }
+    }
+}
+
+/// Which pixels a pass iterates and adds to the accumulation buffer (the `SAMPLE_*` constants in `common.wgsl`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplePixels {
+    /// Every pixel.
+    All = 0,
+    /// Every pixel is iterated, but only the ones which aren't on an edge get added, since the edges get supersampled later.
+    Smooth = 1,
+    /// Only the pixels on an edge.
+    Edges = 2,
+}
+
+/// One of the passes over the image that a frame is split into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplePass {
+    /// The offset of the point iterated from the centre of each pixel, in pixels.
+    pub offset: [f32; 2],
+    pub pixels: SamplePixels,
+    /// Whether the colours get added to what's already in the accumulation buffer, rather than replacing it.
+    pub accumulate: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Supersampling {
+    pub pattern: Pattern,
+    /// Whether only the pixels on edges get supersampled.
+    pub adaptive: bool,
+    /// How many iterations apart a pixel's neighbours have to be to count it as an edge, when `adaptive` is set.
+    pub threshold: u32,
+}
+
+impl Supersampling {
+    /// Creates non-adaptive supersampling with `pattern`.
+    pub fn new(pattern: Pattern) -> Self {
+        Self {
+            pattern,
+            adaptive: false,
+            threshold: DEFAULT_EDGE_THRESHOLD,
+        }
+    }
+
+    /// The passes needed to render a frame with `supersampling`, or just one sample in the centre of each pixel without it.
+    pub fn passes(supersampling: Option<&Self>) -> Vec<SamplePass> {
+        let center = SamplePass {
+            offset: [0.0, 0.0],
+            pixels: SamplePixels::All,
+            accumulate: false,
+        };
+
+        match supersampling {
+            None => vec![center],
+            Some(supersampling) if supersampling.adaptive => {
+                let samples =
+                    supersampling
+                        .pattern
+                        .offsets()
+                        .into_iter()
+                        .map(|offset| SamplePass {
+                            offset,
+                            pixels: SamplePixels::Edges,
+                            accumulate: true,
+                        });
+                iter::once(SamplePass {
+                    pixels: SamplePixels::Smooth,
+                    ..center
+                })
+                .chain(samples)
+                .collect()
+            }
+            Some(supersampling) => supersampling
+                .pattern
+                .offsets()
+                .into_iter()
+                .enumerate()
+                .map(|(i, offset)| SamplePass {
+                    offset,
+                    accumulate: i > 0,
+                    ..center
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The pass which adds the extra sample for the `frame`th frame the view has stayed still for, where the first is 0.
+pub fn temporal_pass(frame: u32, supersampling: Option<&Supersampling>) -> SamplePass {
+    let adaptive = supersampling.is_some_and(|supersampling| supersampling.adaptive);
+    SamplePass {
+        offset: temporal_offset(frame),
+        pixels: if adaptive {
+            SamplePixels::Edges
+        } else {
+            SamplePixels::All
+        },
+        accumulate: true,
+    }
+}
+
+/// The offset of the extra sample for the `frame`th frame the view has stayed still for.
+///
+/// This follows the R2 sequence from http://extremelearning.com.au/unreasonable-effectiveness-of-quasirandom-sequences/,
+/// which fills in the pixel evenly however many frames there end up being.
+pub fn temporal_offset(frame: u32) -> [f32; 2] {
+    // 1/g and 1/g², where g is the plastic number.
+    const ALPHA: [f64; 2] = [0.7548776662466927, 0.5698402909980532];
+    let n = frame as f64 + 1.0;
+    ALPHA.map(|alpha| ((0.5 + alpha * n).fract() - 0.5) as f32)
+}
+
+/// The offsets of an n×n grid of samples, where `position` gives where each one goes within its cell, from 0 to 1.
+fn grid(size: u32, position: impl Fn(u32, u32) -> [f32; 2]) -> Vec<[f32; 2]> {
+    let size = size.max(1);
+    let mut offsets = Vec::new();
+    for y in 0..size {
+        for x in 0..size {
+            let [u, v] = position(x, y);
+            offsets.push([
+                (x as f32 + u) / size as f32 - 0.5,
+                (y as f32 + v) / size as f32 - 0.5,
+            ]);
+        }
+    }
+    offsets
+}
+
+/// A pseudo-random number from 0 to 1 from `seed`, so that jittered samples land in the same places every frame.
+fn hash(seed: u32) -> f32 {
+    // The integer hash from https://nullprogram.com/blog/2018/07/31/.
+    let mut x = seed;
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    (x >> 8) as f32 / (1 << 24) as f32
+}
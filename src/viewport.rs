@@ -0,0 +1,52 @@
+//! Rectangles of the window which get iterated and coloured separately, so that more than one view can be shown at once,
+//! like the preview of the Julia set under the cursor in the corner of the Mandelbrot set.
+//!
+//! Each viewport has its own settings uniform, along with the buffers holding its pixels and the bind groups using them.
+//! Everything else (the pipelines, the reference orbit, the palettes and layers) is shared, since viewports are rendered one after the other.
+//! They all get drawn in the same render pass at the end of the frame, each restricted to its own rectangle.
+
+use wgpu::BindGroup;
+use wgpu::Buffer;
+use wgpu::RenderBundle;
+
+/// How big the Julia set preview is next to the window, in each dimension.
+pub const PREVIEW_SCALE: f32 = 0.25;
+
+/// The most iterations the Julia set preview uses, so that it keeps up with the cursor even when the main view needs far more.
+pub const MAX_PREVIEW_ITERATIONS: u32 = 1000;
+
+#[derive(Debug)]
+pub struct Viewport {
+    /// The position of the viewport's top-left corner in the window, in physical pixels.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+
+    pub settings_buffer: Buffer,
+    /// The results of iterating each pixel, which get turned into colours by the accumulate pipeline.
+    pub pixel_buffer: Buffer,
+    /// The cumulative histogram of pixels' iteration counts, for when `coloring` is `Histogram`.
+    pub histogram_buffer: Buffer,
+    /// The sum of each pixel's samples' colours, which `fs_main` averages (see `supersampling.rs`).
+    pub accumulation_buffer: Buffer,
+
+    pub iterate_bind_group: BindGroup,
+    pub colorize_bind_group: BindGroup,
+    /// Draws the accumulation buffer onto whichever part of the screen the render pass's viewport is set to.
+    pub render_bundle: RenderBundle,
+}
+
+impl Viewport {
+    /// The position and size of the Julia set preview in this viewport's bottom-right corner.
+    pub fn preview_rect(&self) -> [u32; 4] {
+        let preview_width = ((self.width as f32 * PREVIEW_SCALE) as u32).max(1);
+        let preview_height = ((self.height as f32 * PREVIEW_SCALE) as u32).max(1);
+        [
+            self.x + self.width.saturating_sub(preview_width),
+            self.y + self.height.saturating_sub(preview_height),
+            preview_width,
+            preview_height,
+        ]
+    }
+}